@@ -0,0 +1,77 @@
+use std::{env, time::Duration};
+
+use crate::{
+    resources::{BoundaryMode, EndCondition},
+    utils::{MAX_TICK_RATE, MIN_TICK_RATE},
+};
+
+// options parsed from the command line, e.g. `cargo run -- --headless`
+#[derive(Debug, Default)]
+pub struct CliArgs {
+    pub headless: bool,
+    pub seed: Option<u64>,
+    pub tick_rate: Option<f64>,
+    pub results_countdown: Option<f32>,
+    // file name in `assets/rulesets`, without the extension
+    pub ruleset: Option<String>,
+    pub end_condition: Option<EndCondition>,
+    pub time_limit: Option<f32>,
+    pub max_duration: Option<f32>,
+    pub boundary: Option<BoundaryMode>,
+}
+
+impl CliArgs {
+    pub fn parse() -> Self {
+        let mut args = Self::default();
+        let mut raw_args = env::args().skip(1);
+        while let Some(arg) = raw_args.next() {
+            match arg.as_str() {
+                "--headless" => args.headless = true,
+                "--seed" => args.seed = parse_value(&arg, raw_args.next()),
+                "--tick-rate" => {
+                    args.tick_rate = parse_value_where(&arg, raw_args.next(), |tick_rate| {
+                        (MIN_TICK_RATE..=MAX_TICK_RATE).contains(tick_rate)
+                    })
+                }
+                "--results-countdown" => {
+                    args.results_countdown =
+                        parse_value_where(&arg, raw_args.next(), is_valid_duration)
+                }
+                "--ruleset" => args.ruleset = parse_value(&arg, raw_args.next()),
+                "--end-condition" => args.end_condition = parse_value(&arg, raw_args.next()),
+                "--time-limit" => {
+                    args.time_limit = parse_value_where(&arg, raw_args.next(), is_valid_duration)
+                }
+                "--max-duration" => {
+                    args.max_duration = parse_value_where(&arg, raw_args.next(), is_valid_duration)
+                }
+                "--boundary" => args.boundary = parse_value(&arg, raw_args.next()),
+                unknown => eprintln!("ignoring unknown argument {unknown}"),
+            }
+        }
+        args
+    }
+}
+
+fn parse_value<T: std::str::FromStr>(name: &str, value: Option<String>) -> Option<T> {
+    parse_value_where(name, value, |_| true)
+}
+
+// like `parse_value`, but also ignores values that parse and fail `is_valid`
+fn parse_value_where<T: std::str::FromStr>(
+    name: &str,
+    value: Option<String>,
+    is_valid: impl Fn(&T) -> bool,
+) -> Option<T> {
+    let value = value.unwrap_or_default();
+    let parsed = value.parse().ok().filter(is_valid);
+    if parsed.is_none() {
+        eprintln!("ignoring {name}, `{value}` is not a valid value for it");
+    }
+    parsed
+}
+
+// fits in a `Duration`, so not negative, NaN or absurdly long
+fn is_valid_duration(seconds: &f32) -> bool {
+    Duration::try_from_secs_f32(*seconds).is_ok()
+}
@@ -1,44 +1,189 @@
-use bevy::prelude::*;
+use std::f32::consts::TAU;
+
+use bevy::{prelude::*, utils::Duration};
 use rand::prelude::*;
 
-pub trait AssociatedString {
-    const STRING: &'static str;
-}
+use crate::utils::{ANIMATION_FPS, FIELD_OF_VIEW_DEGREES, PERCEPTION_RADIUS};
 
-#[derive(Component, Default)]
-pub struct Rock;
+// which species an entity belongs to, an index into the `FoodChain`
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Species(pub u8);
 
-impl AssociatedString for Rock {
-    const STRING: &'static str = "rock";
-}
+// every entity with a `Species` will include this component
+#[derive(Component)]
+pub struct IsInFoodChain;
 
-#[derive(Component, Default)]
-pub struct Paper;
+#[derive(Component)]
+pub struct Velocity(pub Vec3);
+
+// where the simulation has an entity as of the latest fixed tick.
+// the transform is only for rendering and is interpolated between ticks
+#[derive(Component)]
+pub struct Position(pub Vec3);
+
+#[derive(Component)]
+pub struct PreviousPosition(pub Vec3);
 
-impl AssociatedString for Paper {
-    const STRING: &'static str = "paper";
+// which way an entity faces, in radians counterclockwise from the positive x axis
+#[derive(Component)]
+pub struct Angle(pub f32);
+
+impl Angle {
+    pub fn new(rng: &mut impl Rng) -> Self {
+        Self(rng.gen::<f32>() * TAU)
+    }
+
+    // the way the entity is looking. std's sin and cos may round differently per platform,
+    // libm's don't, which keeps a seed replaying the same everywhere
+    pub fn facing(&self) -> Vec3 {
+        Vec3::new(libm::cosf(self.0), libm::sinf(self.0), 0.0)
+    }
 }
 
-#[derive(Component, Default)]
-pub struct Scissors;
+// how fast an entity is turning, in radians per second, counterclockwise
+#[derive(Component)]
+pub struct AngularVelocity(pub f32);
 
-impl AssociatedString for Scissors {
-    const STRING: &'static str = "scissors";
+// the fastest an entity can turn, in radians per second
+#[derive(Component, Clone, Copy)]
+pub struct MaxTurnRate(pub f32);
+
+// where on its wander circle an entity is aiming, in radians, see `steering::wander`
+#[derive(Component)]
+pub struct Wander(pub f32);
+
+impl Wander {
+    pub fn new(rng: &mut impl Rng) -> Self {
+        Self(rng.gen::<f32>() * TAU)
+    }
 }
 
-// all Rock, Paper, or Scissors bundles will include this component
+// an entity that was just caught, playing its conversion before becoming the predator's
+// species. it can't move or be caught again until the timer finishes
 #[derive(Component)]
-pub struct IsInFoodChain;
+pub struct Converting {
+    pub from: Species,
+    pub to: Species,
+    pub timer: Timer,
+}
+
+impl Converting {
+    // which species it looks like, the old one for the first half and the new one after
+    pub fn showing(&self) -> Species {
+        if self.timer.percent() < 0.5 {
+            self.from
+        } else {
+            self.to
+        }
+    }
+}
 
+// where an entity came from and everything that has happened to it since.
+// conversions only change its species, so this stays with it all match
 #[derive(Component)]
-pub struct Velocity(pub Vec3);
+pub struct Lineage {
+    pub original: Species,
+    // every time it was caught, oldest first
+    pub conversions: Vec<Conversion>,
+}
+
+pub struct Conversion {
+    pub captor: Entity,
+    // on the simulation clock
+    pub at: Duration,
+    // the captor's species, which it turned into
+    pub to: Species,
+}
+
+impl Lineage {
+    pub fn new(species: Species) -> Self {
+        Self {
+            original: species,
+            conversions: vec![],
+        }
+    }
+
+    pub fn conversion_count(&self) -> usize {
+        self.conversions.len()
+    }
+
+    // how long it stayed its original species, if it ever got caught
+    pub fn first_caught(&self) -> Option<Duration> {
+        self.conversions.first().map(|conversion| conversion.at)
+    }
+}
+
+// what an entity is up to, decided by its steering and shown by its animation
+#[derive(Component, Clone, Copy, PartialEq, Eq)]
+pub enum Behavior {
+    Wandering,
+    Fleeing,
+    Chasing,
+}
 
+// the rows of a species' sprite sheet, in order
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Clip {
+    Idle,
+    Move,
+    Flee,
+    Chase,
+    Converting,
+}
+
+impl Clip {
+    pub const COUNT: usize = 5;
+
+    pub fn row(self) -> usize {
+        self as usize
+    }
+}
+
+// which frame of which clip an entity's sprite is showing
 #[derive(Component)]
-pub struct Angle(pub f32);
+pub struct SpriteAnimation {
+    pub clip: Clip,
+    pub frame: usize,
+    pub timer: Timer,
+}
 
-impl Angle {
-    pub fn new() -> Self {
-        Self(random::<f32>() * 360.0)
+impl Default for SpriteAnimation {
+    fn default() -> Self {
+        Self {
+            clip: Clip::Idle,
+            frame: 0,
+            timer: Timer::from_seconds(1.0 / ANIMATION_FPS, TimerMode::Repeating),
+        }
+    }
+}
+
+// how far an entity can see, and how wide a cone around where its `Angle` faces
+#[derive(Component, Clone, Copy)]
+pub struct Perception {
+    pub radius: f32,
+    pub fov_degrees: f32,
+}
+
+impl Default for Perception {
+    fn default() -> Self {
+        Self {
+            radius: PERCEPTION_RADIUS,
+            fov_degrees: FIELD_OF_VIEW_DEGREES,
+        }
+    }
+}
+
+impl Perception {
+    // `offset` is the way from the entity to whatever it's looking for
+    pub fn can_see(&self, facing: Vec3, offset: Vec3) -> bool {
+        if offset.length() > self.radius {
+            return false;
+        }
+        // compares cosines, `angle_between` would need std's acos
+        self.fov_degrees >= 360.0
+            || offset == Vec3::ZERO
+            || facing.normalize_or_zero().dot(offset.normalize())
+                >= libm::cosf((self.fov_degrees / 2.0).to_radians())
     }
 }
 
@@ -48,13 +193,54 @@ pub struct MainMenu;
 #[derive(Component)]
 pub struct PlayButton;
 
+// nudges a species' starting count in the main menu
+#[derive(Component)]
+pub struct CountButton {
+    pub species: Species,
+    pub change: i32,
+}
+
+#[derive(Component)]
+pub struct CountText {
+    pub species: Species,
+}
+
+// holds one count spinner per species, rebuilt whenever the food chain changes
+#[derive(Component)]
+pub struct CountSpinners;
+
+#[derive(Component)]
+pub struct RulesetButton;
+
+#[derive(Component)]
+pub struct EndConditionButton;
+
+#[derive(Component)]
+pub struct BoundaryButton;
+
 #[derive(Component)]
 pub struct PauseButton;
 
+#[derive(Component)]
+pub struct SpeedButton {
+    pub speed: f32,
+}
+
+// holds the buttons shown while a simulation is running
+#[derive(Component)]
+pub struct SimulationControls;
+
 #[derive(Component)]
 pub struct SimulationPage;
 
+#[derive(Component)]
+pub struct PlayAgainButton;
+
+#[derive(Component)]
+pub struct CountdownText;
+
+// counts down to going back to the main menu from the simulation over page
 #[derive(Resource)]
 pub struct SimulationOverTimer {
-    timer: Timer,
+    pub timer: Timer,
 }
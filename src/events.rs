@@ -0,0 +1,11 @@
+use bevy::utils::Duration;
+
+use crate::components::Species;
+
+// fired once a simulation has been decided
+pub struct SimulationEnded {
+    pub winner: Option<Species>,
+    pub duration: Duration,
+    // ran out of `SimulationConfig::max_duration` before its end condition decided it
+    pub cut_off: bool,
+}
@@ -1,16 +1,38 @@
+#![allow(clippy::type_complexity, clippy::too_many_arguments)]
+
+mod cli;
 mod components;
+mod events;
+mod resources;
+mod ruleset;
 mod startup_systems;
+mod steering;
 mod systems;
 mod utils;
 
-use components::{Paper, Rock, Scissors};
+use cli::CliArgs;
+use events::SimulationEnded;
+use resources::{
+    Arena, BoundaryMode, EndCondition, FoodChain, RulesetHandles, SimulationClock,
+    SimulationConfig, SimulationResult, SimulationRng, SimulationSeed, SimulationSpeed,
+    SpatialGrid, SpeciesAssets,
+};
 
 use bevy::prelude::*;
+use bevy::utils::Duration;
+use rand::prelude::*;
+use ruleset::{Ruleset, RulesetLoader};
 use systems::{despawn_main_menu, spawn_main_menu};
+use utils::{
+    DEFAULT_MAX_DURATION, DEFAULT_RESULTS_COUNTDOWN, DEFAULT_RULESET, DEFAULT_TICK_RATE,
+    DEFAULT_TIME_LIMIT,
+};
 
 #[derive(Debug, Clone, Eq, PartialEq, Hash, Default, States)]
 pub enum AppState {
+    // waits for every asset before showing anything, see `LoadingPlugin`
     #[default]
+    Loading,
     MainMenu,
     SimulationRunning,
     SimulationOver,
@@ -23,38 +45,172 @@ pub enum PlayState {
     Paused,
 }
 
+// the stages of a single simulation tick, in the order they run
+#[derive(SystemSet, Debug, Hash, PartialEq, Eq, Clone)]
+pub enum SimulationSet {
+    Indexing,
+    Steering,
+    Integration,
+    Separation,
+    Containment,
+    Conversion,
+    EndCheck,
+}
+
 pub struct SimulationPlugin;
 
 impl Plugin for SimulationPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(
-            (startup_systems::spawn_entities, systems::spawn_play_toggle)
-                .in_schedule(OnEnter(AppState::SimulationRunning)),
-        )
-        .add_systems((systems::play_toggle_interaction,))
-        .add_systems(
-            (
-                systems::entity_movement::<Rock, Paper, Scissors>,
-                systems::entity_movement::<Paper, Scissors, Rock>,
-                systems::entity_movement::<Scissors, Rock, Paper>,
-                systems::contain_entities,
-                systems::detect_collisions_from_predators::<Rock, Paper>,
-                systems::detect_collisions_from_predators::<Paper, Scissors>,
-                systems::detect_collisions_from_predators::<Scissors, Rock>,
-                systems::maintain_personal_space::<Rock>,
-                systems::maintain_personal_space::<Paper>,
-                systems::maintain_personal_space::<Scissors>,
-                systems::is_game_over,
-            )
-                .in_set(OnUpdate(AppState::SimulationRunning))
-                .in_set(OnUpdate(PlayState::Playing)),
+        app.init_resource::<Arena>()
+            .init_resource::<SimulationConfig>()
+            .init_resource::<FoodChain>()
+            .init_resource::<SpatialGrid>()
+            .init_resource::<SimulationSpeed>()
+            .init_resource::<SimulationClock>()
+            .init_resource::<SimulationSeed>()
+            .init_resource::<SpeciesAssets>()
+            .insert_resource(SimulationRng::new(random()))
+            .add_event::<SimulationEnded>()
+            .add_systems(
+                (
+                    startup_systems::seed_simulation_rng,
+                    startup_systems::reset_simulation_clock,
+                    startup_systems::spawn_entities,
+                )
+                    .chain()
+                    .in_schedule(OnEnter(AppState::SimulationRunning)),
+            )
+            .edit_schedule(CoreSchedule::FixedUpdate, |schedule| {
+                let sets = [
+                    SimulationSet::Indexing,
+                    SimulationSet::Steering,
+                    SimulationSet::Integration,
+                    SimulationSet::Separation,
+                    SimulationSet::Containment,
+                    SimulationSet::Conversion,
+                    SimulationSet::EndCheck,
+                ];
+                // every stage only runs while a simulation is playing, and waits on the one before it.
+                // a decided match stops ticking even if more ticks are due this frame
+                for set in sets.iter().cloned() {
+                    schedule.configure_set(
+                        set.run_if(in_state(AppState::SimulationRunning))
+                            .run_if(in_state(PlayState::Playing))
+                            .run_if(not(resource_exists::<SimulationResult>())),
+                    );
+                }
+                for pair in sets.windows(2) {
+                    schedule.configure_set(pair[0].clone().before(pair[1].clone()));
+                }
+            })
+            .add_system(
+                systems::rebuild_spatial_grid
+                    .in_set(SimulationSet::Indexing)
+                    .in_schedule(CoreSchedule::FixedUpdate),
+            )
+            .add_systems(
+                (systems::store_previous_positions, systems::entity_movement)
+                    .in_set(SimulationSet::Steering)
+                    .in_schedule(CoreSchedule::FixedUpdate),
+            )
+            .add_systems(
+                (
+                    systems::advance_simulation_clock,
+                    systems::integrate_velocity,
+                )
+                    .in_set(SimulationSet::Integration)
+                    .in_schedule(CoreSchedule::FixedUpdate),
+            )
+            .add_system(
+                systems::maintain_personal_space
+                    .in_set(SimulationSet::Separation)
+                    .in_schedule(CoreSchedule::FixedUpdate),
+            )
+            .add_system(
+                systems::contain_entities
+                    .in_set(SimulationSet::Containment)
+                    .in_schedule(CoreSchedule::FixedUpdate),
+            )
+            .add_systems(
+                (
+                    systems::finish_conversions,
+                    systems::detect_collisions_from_predators,
+                )
+                    .chain()
+                    .in_set(SimulationSet::Conversion)
+                    .in_schedule(CoreSchedule::FixedUpdate),
+            )
+            // conversions are commands, apply them so the end check counts the new species
+            .add_system(
+                apply_system_buffers
+                    .after(SimulationSet::Conversion)
+                    .before(SimulationSet::EndCheck)
+                    .in_schedule(CoreSchedule::FixedUpdate),
+            )
+            .add_system(
+                systems::is_game_over
+                    .in_set(SimulationSet::EndCheck)
+                    .in_schedule(CoreSchedule::FixedUpdate),
+            )
+            .add_system(
+                systems::interpolate_transforms
+                    .in_set(OnUpdate(AppState::SimulationRunning))
+                    .in_set(OnUpdate(PlayState::Playing)),
+            )
+            .add_system(
+                systems::animate_conversions
+                    .after(systems::interpolate_transforms)
+                    .in_set(OnUpdate(AppState::SimulationRunning))
+                    .in_set(OnUpdate(PlayState::Playing)),
+            )
+            .add_system(
+                systems::animate_sprites
+                    .after(systems::animate_conversions)
+                    .in_set(OnUpdate(AppState::SimulationRunning))
+                    .in_set(OnUpdate(PlayState::Playing)),
+            )
+            .add_system(
+                systems::face_velocity
+                    .after(systems::interpolate_transforms)
+                    .in_set(OnUpdate(AppState::SimulationRunning))
+                    .in_set(OnUpdate(PlayState::Playing)),
+            )
+            .add_system(
+                systems::apply_simulation_speed.in_set(OnUpdate(AppState::SimulationRunning)),
+            )
+            .add_systems(
+                (
+                    startup_systems::despawn_entities,
+                    systems::reset_simulation_speed,
+                    systems::reset_play_state,
+                )
+                    .in_schedule(OnExit(AppState::SimulationRunning)),
+            );
+    }
+}
+
+// on-screen controls shown while a simulation is running
+pub struct SimulationControlsPlugin;
+
+impl Plugin for SimulationControlsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system(
+            systems::spawn_simulation_controls.in_schedule(OnEnter(AppState::SimulationRunning)),
         )
+        .add_systems((
+            systems::play_toggle_interaction,
+            systems::speed_button_interaction,
+            systems::update_speed_buttons,
+        ))
         .add_systems(
             (
-                systems::despawn_play_toggle,
-                startup_systems::despawn_entities,
+                systems::speed_keyboard_shortcuts,
+                systems::leave_simulation_shortcut,
             )
-                .in_schedule(OnExit(AppState::SimulationRunning)),
+                .in_set(OnUpdate(AppState::SimulationRunning)),
+        )
+        .add_system(
+            systems::despawn_simulation_controls.in_schedule(OnExit(AppState::SimulationRunning)),
         );
     }
 }
@@ -71,7 +227,11 @@ impl Plugin for SimulationOverPage {
                 .in_schedule(OnEnter(AppState::SimulationOver)),
         )
         .add_systems(
-            (systems::tick_simulation_over_timer,).in_set(OnUpdate(AppState::SimulationOver)),
+            (
+                systems::tick_simulation_over_timer,
+                systems::play_again_button_interaction,
+            )
+                .in_set(OnUpdate(AppState::SimulationOver)),
         )
         .add_systems(
             (
@@ -88,19 +248,144 @@ pub struct MainMenuPlugin;
 impl Plugin for MainMenuPlugin {
     fn build(&self, app: &mut App) {
         app.add_system(spawn_main_menu.in_schedule(OnEnter(AppState::MainMenu)))
-            .add_system(systems::play_button_interaction);
-            .add_system(despawn_main_menu.in_schedule(OnExit(AppState::MainMenu)))
+            .add_systems((
+                systems::play_button_interaction,
+                systems::count_button_interaction,
+                systems::update_count_text,
+                systems::ruleset_button_interaction,
+                systems::update_ruleset_text,
+                systems::rebuild_count_spinners,
+                systems::end_condition_button_interaction,
+                systems::boundary_button_interaction,
+            ))
+            .add_system(despawn_main_menu.in_schedule(OnExit(AppState::MainMenu)));
+    }
+}
+
+// loads the font, every ruleset and all of their species' sprites and sounds
+// before the main menu shows up, so switching rulesets never waits on a file.
+// a ruleset edited later may name new files, those are picked up when the food chain changes
+pub struct LoadingPlugin;
+
+impl Plugin for LoadingPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system(startup_systems::load_font.in_schedule(OnEnter(AppState::Loading)))
+            .add_systems(
+                (
+                    startup_systems::load_species_assets,
+                    systems::wait_for_assets,
+                )
+                    .chain()
+                    .in_set(OnUpdate(AppState::Loading)),
+            )
+            .add_system(
+                startup_systems::load_species_assets.run_if(resource_changed::<FoodChain>()),
+            );
+    }
+}
+
+// frames the arena, or follows the crowd when it has no edges
+pub struct CameraPlugin;
+
+impl Plugin for CameraPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_startup_system(startup_systems::spawn_camera)
+            .add_system(systems::follow_crowd.in_set(OnUpdate(AppState::SimulationRunning)))
+            .add_system(systems::reset_camera.in_schedule(OnExit(AppState::SimulationRunning)));
+    }
+}
+
+// every ruleset in `assets/rulesets`, reloaded whenever a file changes
+pub struct RulesetPlugin;
+
+impl Plugin for RulesetPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_asset::<Ruleset>()
+            .init_asset_loader::<RulesetLoader>()
+            .init_resource::<RulesetHandles>()
+            .add_startup_system(startup_systems::load_rulesets)
+            .add_system(systems::reload_ruleset);
+    }
+}
+
+// runs a single match without a window, skipping the menus, and exits with the winner
+pub struct HeadlessPlugin;
+
+impl Plugin for HeadlessPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(NextState(Some(AppState::SimulationRunning)))
+            .add_system(systems::step_fixed_time.in_base_set(CoreSet::First))
+            .add_system(systems::exit_on_simulation_end);
     }
 }
 
 fn main() {
-    App::new()
-        .add_plugins(DefaultPlugins)
-        .add_state::<AppState>()
-        .add_state::<PlayState>()
-        .add_startup_system(startup_systems::spawn_camera)
-        .add_plugin(SimulationPlugin)
-        .add_plugin(SimulationOverPage)
-        .add_plugin(MainMenuPlugin)
+    let args = CliArgs::parse();
+
+    let mut app = App::new();
+
+    if args.headless {
+        app.add_plugins(MinimalPlugins);
+    } else {
+        app.add_plugins(DefaultPlugins.set(AssetPlugin {
+            watch_for_changes: true,
+            ..default()
+        }));
+    }
+
+    app.add_state::<AppState>().add_state::<PlayState>();
+
+    if args.headless {
+        app.add_plugin(HeadlessPlugin);
+    } else {
+        app.add_plugin(LoadingPlugin)
+            .add_plugin(CameraPlugin)
+            .add_plugin(RulesetPlugin)
+            .add_plugin(SimulationControlsPlugin)
+            .add_plugin(SimulationOverPage)
+            .add_plugin(MainMenuPlugin);
+    }
+
+    let tick_rate = args.tick_rate.unwrap_or(DEFAULT_TICK_RATE);
+
+    let mut simulation_config = SimulationConfig {
+        results_countdown: args.results_countdown.unwrap_or(DEFAULT_RESULTS_COUNTDOWN),
+        end_condition: args.end_condition.unwrap_or(EndCondition::FirstExtinction),
+        time_limit: Duration::from_secs_f32(args.time_limit.unwrap_or(DEFAULT_TIME_LIMIT)),
+        max_duration: args.max_duration.map(Duration::from_secs_f32),
+        ..default()
+    };
+    // nobody is there to stop a headless match by hand, so it always gets cut off eventually
+    if args.headless && simulation_config.max_duration.is_none() {
+        if simulation_config.end_condition == EndCondition::Sandbox {
+            eprintln!("a sandbox match never ends, pass --max-duration to run one with --headless");
+            std::process::exit(1);
+        }
+        simulation_config.max_duration = Some(Duration::from_secs_f32(DEFAULT_MAX_DURATION));
+    }
+    let mut food_chain = FoodChain::default();
+
+    // read up front so the first match is on the requested rules even without an asset server
+    let ruleset_name = args.ruleset.unwrap_or_else(|| DEFAULT_RULESET.to_string());
+    if let Err(e) = Ruleset::from_file(&ruleset_name)
+        .and_then(|ruleset| simulation_config.use_ruleset(&ruleset_name, &ruleset, &mut food_chain))
+    {
+        eprintln!(
+            "couldn't load ruleset {}, playing {} instead: {}",
+            ruleset_name,
+            food_chain.ruleset_name(),
+            e
+        );
+    }
+
+    app.add_plugin(SimulationPlugin)
+        .insert_resource(Arena {
+            boundary: args.boundary.unwrap_or(BoundaryMode::Wrap),
+            ..default()
+        })
+        .insert_resource(simulation_config)
+        .insert_resource(food_chain)
+        .insert_resource(SimulationSeed(args.seed))
+        .insert_resource(FixedTime::new(Duration::from_secs_f64(1.0 / tick_rate)))
         .run();
 }
@@ -0,0 +1,817 @@
+use std::{fmt, str::FromStr};
+
+use bevy::{
+    asset::HandleId,
+    prelude::*,
+    utils::{Duration, HashMap},
+};
+use rand::prelude::*;
+use rand_chacha::ChaCha8Rng;
+
+use crate::components::{Clip, Lineage, MaxTurnRate, Perception, Species};
+use crate::ruleset::Ruleset;
+use crate::utils::{
+    DEFAULT_ENTITY_COUNT, DEFAULT_RESULTS_COUNTDOWN, DEFAULT_RULESET, DEFAULT_SPRITE_FORWARD,
+    DEFAULT_TIME_LIMIT, ENTITY_HALF_SIZE, ENTITY_MAX_FORCE, ENTITY_MAX_SPEED, ENTITY_MAX_TURN_RATE,
+    ENTITY_SIZE, SPATIAL_GRID_CELL_SIZE,
+};
+
+// the playing field, in world units, that entities are spawned in and wrapped around.
+// it is independent of the window so a match plays the same on any display size
+#[derive(Resource)]
+pub struct Arena {
+    pub width: f32,
+    pub height: f32,
+    // bottom left corner
+    pub origin: Vec2,
+    pub boundary: BoundaryMode,
+}
+
+impl Arena {
+    pub fn center(&self) -> Vec2 {
+        self.origin + Vec2::new(self.width, self.height) / 2.0
+    }
+
+    pub fn wraps(&self) -> bool {
+        self.boundary == BoundaryMode::Wrap
+    }
+
+    // the furthest in an entity's center can go when the edges are solid
+    pub fn inner_min(&self) -> Vec2 {
+        self.origin + Vec2::splat(ENTITY_HALF_SIZE)
+    }
+
+    pub fn inner_max(&self) -> Vec2 {
+        self.origin + Vec2::new(self.width, self.height) - Vec2::splat(ENTITY_HALF_SIZE)
+    }
+
+    // entities only wrap once they are fully past an edge
+    pub fn wrap_min(&self) -> Vec2 {
+        self.origin - Vec2::splat(ENTITY_HALF_SIZE)
+    }
+
+    pub fn wrap_size(&self) -> Vec2 {
+        Vec2::new(self.width, self.height) + Vec2::splat(ENTITY_SIZE)
+    }
+
+    // the shortest way from one point to another, across the edges if the arena wraps
+    pub fn offset(&self, from: Vec3, to: Vec3) -> Vec3 {
+        let mut offset = to - from;
+        if self.wraps() {
+            let size = self.wrap_size();
+            offset.x -= size.x * (offset.x / size.x).round();
+            offset.y -= size.y * (offset.y / size.y).round();
+        }
+        offset
+    }
+
+    pub fn distance(&self, a: Vec3, b: Vec3) -> f32 {
+        self.offset(a, b).length()
+    }
+}
+
+impl Default for Arena {
+    fn default() -> Self {
+        Self {
+            width: 1280.0,
+            height: 720.0,
+            origin: Vec2::ZERO,
+            boundary: BoundaryMode::Wrap,
+        }
+    }
+}
+
+// what happens at the edges of the arena
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BoundaryMode {
+    // leave one edge and come back on the opposite one
+    Wrap,
+    // edges reflect anything that hits them
+    Bounce,
+    // edges stop anything that hits them, and entities steer clear of them
+    Wall,
+    // no edges at all, the camera follows the crowd instead
+    Infinite,
+}
+
+impl BoundaryMode {
+    pub const ALL: [BoundaryMode; 4] = [
+        BoundaryMode::Wrap,
+        BoundaryMode::Bounce,
+        BoundaryMode::Wall,
+        BoundaryMode::Infinite,
+    ];
+
+    pub fn next(self) -> Self {
+        let index = Self::ALL.iter().position(|b| *b == self).unwrap_or(0);
+        Self::ALL[(index + 1) % Self::ALL.len()]
+    }
+}
+
+impl fmt::Display for BoundaryMode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            BoundaryMode::Wrap => "wrap",
+            BoundaryMode::Bounce => "bounce",
+            BoundaryMode::Wall => "wall",
+            BoundaryMode::Infinite => "infinite",
+        })
+    }
+}
+
+impl FromStr for BoundaryMode {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "wrap" => Ok(BoundaryMode::Wrap),
+            "bounce" => Ok(BoundaryMode::Bounce),
+            "wall" => Ok(BoundaryMode::Wall),
+            "infinite" => Ok(BoundaryMode::Infinite),
+            _ => Err(()),
+        }
+    }
+}
+
+// buckets every entity into cells, rebuilt at the start of every tick,
+// so neighbour lookups only look at nearby cells instead of the whole arena
+#[derive(Resource)]
+pub struct SpatialGrid {
+    cell_size: Vec2,
+    // where cell (0, 0) starts
+    origin: Vec2,
+    // how many cells fit across a wrapping arena, cells past that fold back around
+    wrapped_cells: Option<IVec2>,
+    cells: HashMap<IVec2, Vec<GridEntry>>,
+}
+
+// where an entity was when the grid was built
+#[derive(Clone, Copy)]
+pub struct GridEntry {
+    pub entity: Entity,
+    pub species: Species,
+    pub position: Vec3,
+}
+
+impl SpatialGrid {
+    pub fn rebuild(&mut self, arena: &Arena, entries: impl Iterator<Item = GridEntry>) {
+        if arena.wraps() {
+            // stretch the cells a little so a whole number of them spans the wrap
+            let size = arena.wrap_size();
+            let cells = (size / SPATIAL_GRID_CELL_SIZE)
+                .ceil()
+                .as_ivec2()
+                .max(IVec2::ONE);
+            self.cell_size = size / cells.as_vec2();
+            self.origin = arena.wrap_min();
+            self.wrapped_cells = Some(cells);
+        } else {
+            self.cell_size = Vec2::splat(SPATIAL_GRID_CELL_SIZE);
+            self.origin = Vec2::ZERO;
+            self.wrapped_cells = None;
+        }
+
+        // an unbounded arena could leave behind an ever growing number of empty cells
+        self.cells.retain(|_, cell| !cell.is_empty());
+        for cell in self.cells.values_mut() {
+            cell.clear();
+        }
+        for entry in entries {
+            self.insert(entry);
+        }
+    }
+
+    pub fn insert(&mut self, entry: GridEntry) {
+        let cell = self.cell_of(entry.position);
+        self.cells.entry(cell).or_default().push(entry);
+    }
+
+    fn cell_of(&self, position: Vec3) -> IVec2 {
+        self.wrap_cell(self.unwrapped_cell_of(position))
+    }
+
+    fn unwrapped_cell_of(&self, position: Vec3) -> IVec2 {
+        ((position.truncate() - self.origin) / self.cell_size)
+            .floor()
+            .as_ivec2()
+    }
+
+    fn wrap_cell(&self, cell: IVec2) -> IVec2 {
+        match self.wrapped_cells {
+            Some(cells) => IVec2::new(cell.x.rem_euclid(cells.x), cell.y.rem_euclid(cells.y)),
+            None => cell,
+        }
+    }
+
+    // everything in the cells overlapping a square of `radius` around `position`.
+    // callers still need to check the actual distance
+    pub fn nearby(&self, position: Vec3, radius: f32) -> impl Iterator<Item = &GridEntry> {
+        let from = self.unwrapped_cell_of(position - Vec3::new(radius, radius, 0.0));
+        let mut to = self.unwrapped_cell_of(position + Vec3::new(radius, radius, 0.0));
+        if let Some(cells) = self.wrapped_cells {
+            // don't go all the way around and visit a cell twice
+            to = to.min(from + cells - IVec2::ONE);
+        }
+        (from.y..=to.y)
+            .flat_map(move |y| (from.x..=to.x).map(move |x| IVec2::new(x, y)))
+            .filter_map(|cell| self.cells.get(&self.wrap_cell(cell)))
+            .flatten()
+    }
+}
+
+impl Default for SpatialGrid {
+    fn default() -> Self {
+        Self {
+            cell_size: Vec2::splat(SPATIAL_GRID_CELL_SIZE),
+            origin: Vec2::ZERO,
+            wrapped_cells: None,
+            cells: HashMap::default(),
+        }
+    }
+}
+
+// how a match is set up, edited from the main menu before pressing play
+#[derive(Resource)]
+pub struct SimulationConfig {
+    // starting count for each species, indexed the same as the food chain
+    pub counts: Vec<u16>,
+    // name of the file in `assets/rulesets` the food chain came from
+    pub ruleset: String,
+    pub end_condition: EndCondition,
+    // only used by `EndCondition::TimeLimit`
+    pub time_limit: Duration,
+    // a match still going by then is cut off and goes to the largest species,
+    // so headless runs finish even when the survivors can't catch each other
+    pub max_duration: Option<Duration>,
+    // seconds the simulation over page waits before going back to the main menu
+    pub results_countdown: f32,
+}
+
+impl SimulationConfig {
+    pub fn count(&self, species: Species) -> u16 {
+        self.counts
+            .get(species.0 as usize)
+            .copied()
+            .unwrap_or(DEFAULT_ENTITY_COUNT)
+    }
+
+    pub fn count_mut(&mut self, species: Species) -> &mut u16 {
+        let index = species.0 as usize;
+        if self.counts.len() <= index {
+            self.counts.resize(index + 1, DEFAULT_ENTITY_COUNT);
+        }
+        &mut self.counts[index]
+    }
+
+    // switches to a ruleset, along with the starting counts it suggests
+    pub fn use_ruleset(
+        &mut self,
+        name: &str,
+        ruleset: &Ruleset,
+        food_chain: &mut FoodChain,
+    ) -> Result<(), String> {
+        *food_chain = FoodChain::from_ruleset(ruleset)?;
+        self.ruleset = name.to_string();
+        self.counts = ruleset.species.iter().map(|s| s.count).collect();
+        Ok(())
+    }
+}
+
+impl Default for SimulationConfig {
+    fn default() -> Self {
+        Self {
+            counts: vec![],
+            ruleset: DEFAULT_RULESET.to_string(),
+            end_condition: EndCondition::FirstExtinction,
+            time_limit: Duration::from_secs_f32(DEFAULT_TIME_LIMIT),
+            max_duration: None,
+            results_countdown: DEFAULT_RESULTS_COUNTDOWN,
+        }
+    }
+}
+
+// who is in the match and who eats whom, as a directed graph from predator to prey.
+// built from a `Ruleset`, see `assets/rulesets`
+#[derive(Resource, Clone)]
+pub struct FoodChain {
+    ruleset_name: String,
+    species: Vec<SpeciesInfo>,
+}
+
+#[derive(Clone)]
+struct SpeciesInfo {
+    name: String,
+    sprite: String,
+    sheet: Option<String>,
+    frames: usize,
+    sound: Option<String>,
+    // radians
+    sprite_forward: f32,
+    max_speed: f32,
+    max_force: f32,
+    max_turn_rate: MaxTurnRate,
+    bravery: f32,
+    caution: f32,
+    perception: Perception,
+    prey: Vec<Species>,
+}
+
+impl FoodChain {
+    pub fn from_ruleset(ruleset: &Ruleset) -> Result<Self, String> {
+        let index_of = |name: &str| {
+            ruleset
+                .species
+                .iter()
+                .position(|s| s.name == name)
+                .map(|index| Species(index as u8))
+                .ok_or_else(|| format!("{} is not in the food chain", name))
+        };
+
+        let mut species: Vec<SpeciesInfo> = ruleset
+            .species
+            .iter()
+            .map(|rules| SpeciesInfo {
+                name: rules.name.clone(),
+                sprite: rules.sprite.clone(),
+                sheet: rules.sheet.clone(),
+                frames: rules.frames,
+                sound: rules.sound.clone(),
+                sprite_forward: rules.forward.to_radians(),
+                max_speed: rules.speed,
+                max_force: rules.force,
+                max_turn_rate: MaxTurnRate(rules.turn_rate.to_radians()),
+                bravery: rules.bravery,
+                caution: rules.caution,
+                perception: Perception {
+                    radius: rules.perception,
+                    fov_degrees: rules.fov,
+                },
+                prey: vec![],
+            })
+            .collect();
+        for (predator, prey) in &ruleset.beats {
+            let prey = index_of(prey)?;
+            species[index_of(predator)?.0 as usize].prey.push(prey);
+        }
+
+        Ok(Self {
+            ruleset_name: ruleset.name.clone(),
+            species,
+        })
+    }
+
+    pub fn ruleset_name(&self) -> &str {
+        &self.ruleset_name
+    }
+
+    pub fn species(&self) -> impl Iterator<Item = Species> {
+        (0..self.species.len() as u8).map(Species)
+    }
+
+    pub fn name(&self, species: Species) -> &str {
+        self.species
+            .get(species.0 as usize)
+            .map_or("unknown", |info| info.name.as_str())
+    }
+
+    // the image to draw a species with and how it's cut up, as (path, columns, rows).
+    // a species without a sprite sheet is a single frame used for every clip
+    pub fn sprite_sheet(&self, species: Species) -> (&str, usize, usize) {
+        match self.species.get(species.0 as usize) {
+            Some(SpeciesInfo {
+                sheet: Some(sheet),
+                frames,
+                ..
+            }) => (sheet.as_str(), (*frames).max(1), Clip::COUNT),
+            Some(info) => (info.sprite.as_str(), 1, 1),
+            None => ("", 1, 1),
+        }
+    }
+
+    // played when this species catches something
+    pub fn capture_sound(&self, species: Species) -> Option<&str> {
+        self.species
+            .get(species.0 as usize)
+            .and_then(|info| info.sound.as_deref())
+    }
+
+    // which way the sprite's art points, in radians counterclockwise from the right
+    pub fn sprite_forward(&self, species: Species) -> f32 {
+        self.species
+            .get(species.0 as usize)
+            .map_or(DEFAULT_SPRITE_FORWARD.to_radians(), |info| {
+                info.sprite_forward
+            })
+    }
+
+    // top speed of the quickest species, which bounds how far anything moves in a tick
+    pub fn fastest_speed(&self) -> f32 {
+        self.species
+            .iter()
+            .map(|info| info.max_speed)
+            .fold(0.0, f32::max)
+    }
+
+    pub fn max_speed(&self, species: Species) -> f32 {
+        self.species
+            .get(species.0 as usize)
+            .map_or(ENTITY_MAX_SPEED, |info| info.max_speed)
+    }
+
+    pub fn max_force(&self, species: Species) -> f32 {
+        self.species
+            .get(species.0 as usize)
+            .map_or(ENTITY_MAX_FORCE, |info| info.max_force)
+    }
+
+    // how much prey nearby pulls this species in
+    pub fn bravery(&self, species: Species) -> f32 {
+        self.species
+            .get(species.0 as usize)
+            .map_or(1.0, |info| info.bravery)
+    }
+
+    // how much predators nearby push this species away
+    pub fn caution(&self, species: Species) -> f32 {
+        self.species
+            .get(species.0 as usize)
+            .map_or(1.0, |info| info.caution)
+    }
+
+    pub fn max_turn_rate(&self, species: Species) -> MaxTurnRate {
+        self.species
+            .get(species.0 as usize)
+            .map_or(MaxTurnRate(ENTITY_MAX_TURN_RATE.to_radians()), |info| {
+                info.max_turn_rate
+            })
+    }
+
+    pub fn perception(&self, species: Species) -> Perception {
+        self.species
+            .get(species.0 as usize)
+            .map_or(Perception::default(), |info| info.perception)
+    }
+
+    pub fn eats(&self, predator: Species, prey: Species) -> bool {
+        self.species
+            .get(predator.0 as usize)
+            .is_some_and(|info| info.prey.contains(&prey))
+    }
+}
+
+impl Default for FoodChain {
+    fn default() -> Self {
+        Self::from_ruleset(&Ruleset::default()).expect("the built in ruleset is valid")
+    }
+}
+
+// everything needed to draw and hear the species of every ruleset, plus the UI font,
+// all loaded once before the main menu shows up. headless runs leave it empty
+#[derive(Resource, Default)]
+pub struct SpeciesAssets {
+    pub font: Handle<Font>,
+    // keyed by sprite path, see `FoodChain::sprite_sheet`
+    pub sprites: HashMap<String, SpriteHandles>,
+    // keyed by sound path
+    pub capture_sounds: HashMap<String, Handle<AudioSource>>,
+}
+
+pub struct SpriteHandles {
+    pub texture: Handle<Image>,
+    // `texture` cut into frames
+    pub atlas: Handle<TextureAtlas>,
+    // how `atlas` was cut, which may not match the food chain if a ruleset is edited later
+    pub columns: usize,
+    pub rows: usize,
+}
+
+impl SpeciesAssets {
+    fn sprite(&self, food_chain: &FoodChain, species: Species) -> Option<&SpriteHandles> {
+        let (path, _, _) = food_chain.sprite_sheet(species);
+        self.sprites.get(path)
+    }
+
+    pub fn atlas(&self, food_chain: &FoodChain, species: Species) -> Handle<TextureAtlas> {
+        self.sprite(food_chain, species)
+            .map(|handles| handles.atlas.clone())
+            .unwrap_or_default()
+    }
+
+    // columns and rows of the species' atlas, a single frame if it has none
+    pub fn layout(&self, food_chain: &FoodChain, species: Species) -> (usize, usize) {
+        self.sprite(food_chain, species)
+            .map_or((1, 1), |handles| (handles.columns, handles.rows))
+    }
+
+    pub fn capture_sound(
+        &self,
+        food_chain: &FoodChain,
+        species: Species,
+    ) -> Option<Handle<AudioSource>> {
+        food_chain
+            .capture_sound(species)
+            .and_then(|path| self.capture_sounds.get(path).cloned())
+    }
+
+    // every file behind these handles, for the loading screen to wait on
+    pub fn handle_ids(&self) -> impl Iterator<Item = HandleId> + '_ {
+        let textures = self.sprites.values().map(|handles| handles.texture.id());
+        let sounds = self.capture_sounds.values().map(|sound| sound.id());
+        std::iter::once(self.font.id())
+            .chain(textures)
+            .chain(sounds)
+    }
+}
+
+// keeps every ruleset in `assets/rulesets` loaded so the main menu can cycle through them
+#[derive(Resource, Default)]
+pub struct RulesetHandles(pub Vec<Handle<Ruleset>>);
+
+// when a match counts as over
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EndCondition {
+    // the moment any species dies out, which already decides the winner
+    FirstExtinction,
+    // keep going until only one species is left
+    LastSpeciesStanding,
+    // whoever has the most members when time runs out
+    TimeLimit,
+    // never ends
+    Sandbox,
+}
+
+impl EndCondition {
+    pub const ALL: [EndCondition; 4] = [
+        EndCondition::FirstExtinction,
+        EndCondition::LastSpeciesStanding,
+        EndCondition::TimeLimit,
+        EndCondition::Sandbox,
+    ];
+
+    pub fn next(self) -> Self {
+        let index = Self::ALL.iter().position(|c| *c == self).unwrap_or(0);
+        Self::ALL[(index + 1) % Self::ALL.len()]
+    }
+}
+
+impl fmt::Display for EndCondition {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            EndCondition::FirstExtinction => "first extinction",
+            EndCondition::LastSpeciesStanding => "last species standing",
+            EndCondition::TimeLimit => "time limit",
+            EndCondition::Sandbox => "sandbox",
+        })
+    }
+}
+
+impl FromStr for EndCondition {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "first-extinction" => Ok(EndCondition::FirstExtinction),
+            "last-standing" => Ok(EndCondition::LastSpeciesStanding),
+            "time-limit" => Ok(EndCondition::TimeLimit),
+            "sandbox" => Ok(EndCondition::Sandbox),
+            _ => Err(()),
+        }
+    }
+}
+
+// how many times faster than real time a match plays out
+#[derive(Resource)]
+pub struct SimulationSpeed(pub f32);
+
+impl Default for SimulationSpeed {
+    fn default() -> Self {
+        Self(1.0)
+    }
+}
+
+// simulated time since the match started, which only moves while the simulation ticks
+#[derive(Resource, Default)]
+pub struct SimulationClock(pub Duration);
+
+// how the last match went, shown on the simulation over page.
+// it only exists once a match has been decided
+#[derive(Resource)]
+pub struct SimulationResult {
+    // nobody wins a draw
+    pub winner: Option<Species>,
+    pub duration: Duration,
+    pub end_condition: EndCondition,
+    // ran out of `SimulationConfig::max_duration` instead of being decided by `end_condition`
+    pub cut_off: bool,
+    pub stats: LineageStats,
+}
+
+// the standout entities of a match, worked out from every `Lineage` once it's decided
+pub struct LineageStats {
+    // per species that took part, the original member that held out longest
+    pub longest_surviving: Vec<Survivor>,
+    // the entity that changed sides most, as (original species, what it became each time)
+    pub most_converted: Option<(Species, Vec<Species>)>,
+    // the entity that caught the most others, as (original species, catches)
+    pub best_hunter: Option<(Species, usize)>,
+}
+
+pub struct Survivor {
+    pub species: Species,
+    pub lasted: Duration,
+    // lasted the whole match without being caught
+    pub never_caught: bool,
+}
+
+impl LineageStats {
+    pub fn new<'a>(
+        lineages: impl Iterator<Item = (Entity, &'a Lineage)>,
+        food_chain: &FoodChain,
+        duration: Duration,
+    ) -> Self {
+        let mut longest_surviving: Vec<Option<Survivor>> =
+            food_chain.species().map(|_| None).collect();
+        let mut most_converted: Option<(Species, Vec<Species>)> = None;
+        let mut originals: HashMap<Entity, Species> = HashMap::default();
+        let mut catches: HashMap<Entity, usize> = HashMap::default();
+
+        for (entity, lineage) in lineages {
+            originals.insert(entity, lineage.original);
+            for conversion in &lineage.conversions {
+                *catches.entry(conversion.captor).or_default() += 1;
+            }
+
+            let survivor = Survivor {
+                species: lineage.original,
+                lasted: lineage.first_caught().unwrap_or(duration),
+                never_caught: lineage.first_caught().is_none(),
+            };
+            if let Some(best) = longest_surviving.get_mut(lineage.original.0 as usize) {
+                if best
+                    .as_ref()
+                    .is_none_or(|best| survivor.lasted > best.lasted)
+                {
+                    *best = Some(survivor);
+                }
+            }
+
+            let conversions = lineage.conversion_count();
+            if conversions > 0
+                && most_converted
+                    .as_ref()
+                    .is_none_or(|(_, most)| conversions > most.len())
+            {
+                let became = lineage.conversions.iter().map(|c| c.to).collect();
+                most_converted = Some((lineage.original, became));
+            }
+        }
+
+        // ties go to the lowest entity so the same seed always names the same hunter
+        let best_hunter = catches
+            .into_iter()
+            .max_by_key(|(captor, count)| (*count, std::cmp::Reverse(*captor)))
+            .and_then(|(captor, count)| Some((*originals.get(&captor)?, count)));
+
+        Self {
+            longest_surviving: longest_surviving.into_iter().flatten().collect(),
+            most_converted,
+            best_hunter,
+        }
+    }
+}
+
+// the seed requested on the command line, if any. a fresh one is picked for every match otherwise
+#[derive(Resource, Default)]
+pub struct SimulationSeed(pub Option<u64>);
+
+// every random decision in a match goes through this so it can be replayed from its seed
+#[derive(Resource)]
+pub struct SimulationRng {
+    pub seed: u64,
+    pub rng: ChaCha8Rng,
+}
+
+impl SimulationRng {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            rng: ChaCha8Rng::seed_from_u64(seed),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn arena(boundary: BoundaryMode) -> Arena {
+        Arena {
+            boundary,
+            ..default()
+        }
+    }
+
+    fn grid(arena: &Arena, positions: &[Vec2]) -> SpatialGrid {
+        let mut grid = SpatialGrid::default();
+        grid.rebuild(
+            arena,
+            positions
+                .iter()
+                .enumerate()
+                .map(|(index, position)| GridEntry {
+                    entity: Entity::from_raw(index as u32),
+                    species: Species(0),
+                    position: position.extend(0.0),
+                }),
+        );
+        grid
+    }
+
+    fn times_found(grid: &SpatialGrid, from: Vec2, radius: f32, index: u32) -> usize {
+        grid.nearby(from.extend(0.0), radius)
+            .filter(|entry| entry.entity == Entity::from_raw(index))
+            .count()
+    }
+
+    #[test]
+    fn offset_takes_the_short_way_across_the_wrap() {
+        let arena = arena(BoundaryMode::Wrap);
+        let size = arena.wrap_size();
+        let near_right = Vec3::new(arena.width - 10.0, 100.0, 0.0);
+        let near_left = Vec3::new(10.0, 100.0, 0.0);
+
+        let offset = arena.offset(near_right, near_left);
+        assert!((offset.x - (size.x - arena.width + 20.0)).abs() < 1e-3);
+        assert_eq!(offset.y, 0.0);
+        assert!((arena.offset(near_left, near_right).x + offset.x).abs() < 1e-3);
+
+        let near_top = Vec3::new(100.0, arena.height - 10.0, 0.0);
+        let near_bottom = Vec3::new(100.0, 10.0, 0.0);
+        let offset = arena.offset(near_top, near_bottom);
+        assert!((offset.y - (size.y - arena.height + 20.0)).abs() < 1e-3);
+    }
+
+    #[test]
+    fn offset_is_direct_without_wrapping() {
+        let arena = arena(BoundaryMode::Wall);
+        let from = Vec3::new(arena.width - 10.0, 100.0, 0.0);
+        let to = Vec3::new(10.0, 100.0, 0.0);
+        assert_eq!(arena.offset(from, to), to - from);
+    }
+
+    #[test]
+    fn nearby_finds_neighbours_across_every_edge() {
+        let arena = arena(BoundaryMode::Wrap);
+        let min = arena.wrap_min() + Vec2::splat(4.0);
+        let max = arena.wrap_min() + arena.wrap_size() - Vec2::splat(4.0);
+        let middle = arena.center();
+        // (entity by one edge, query point just across the wrap from it)
+        let pairs = [
+            (Vec2::new(min.x, middle.y), Vec2::new(max.x, middle.y)),
+            (Vec2::new(max.x, middle.y), Vec2::new(min.x, middle.y)),
+            (Vec2::new(middle.x, min.y), Vec2::new(middle.x, max.y)),
+            (Vec2::new(middle.x, max.y), Vec2::new(middle.x, min.y)),
+            (min, max),
+        ];
+        for (entity_at, query_from) in pairs {
+            let grid = grid(&arena, &[entity_at]);
+            assert!(arena.distance(entity_at.extend(0.0), query_from.extend(0.0)) < ENTITY_SIZE);
+            assert_eq!(
+                times_found(&grid, query_from, ENTITY_SIZE, 0),
+                1,
+                "{entity_at} from {query_from}"
+            );
+        }
+    }
+
+    #[test]
+    fn nearby_visits_each_cell_once_when_the_radius_is_wider_than_the_arena() {
+        let arena = arena(BoundaryMode::Wrap);
+        let positions = [
+            Vec2::ZERO,
+            arena.center(),
+            Vec2::new(arena.width, arena.height),
+        ];
+        let grid = grid(&arena, &positions);
+        for (index, _) in positions.iter().enumerate() {
+            assert_eq!(
+                times_found(&grid, arena.center(), arena.width * 3.0, index as u32),
+                1
+            );
+        }
+    }
+
+    #[test]
+    fn nearby_does_not_wrap_solid_edges() {
+        let arena = arena(BoundaryMode::Wall);
+        let grid = grid(&arena, &[Vec2::new(arena.width - 10.0, 100.0)]);
+        assert_eq!(
+            times_found(&grid, Vec2::new(10.0, 100.0), ENTITY_SIZE, 0),
+            0
+        );
+        assert_eq!(
+            times_found(&grid, Vec2::new(arena.width - 40.0, 100.0), ENTITY_SIZE, 0),
+            1
+        );
+    }
+}
@@ -0,0 +1,221 @@
+use std::fs;
+
+use bevy::{
+    asset::{AssetLoader, FileAssetIo, LoadContext, LoadedAsset},
+    reflect::TypeUuid,
+    utils::BoxedFuture,
+};
+use ron::extensions::Extensions;
+use serde::Deserialize;
+
+use crate::resources::FoodChain;
+use crate::utils::{
+    DEFAULT_ENTITY_COUNT, DEFAULT_SHEET_FRAMES, DEFAULT_SPRITE_FORWARD, ENTITY_MAX_FORCE,
+    ENTITY_MAX_SPEED, ENTITY_MAX_TURN_RATE, FIELD_OF_VIEW_DEGREES, MAX_PERCEPTION_RADIUS,
+    MAX_SHEET_FRAMES, MAX_SPEED, PERCEPTION_RADIUS,
+};
+
+// a food chain as designers write it, in `assets/rulesets/<name>.ron`
+#[derive(Deserialize, TypeUuid)]
+#[uuid = "33f213ac-f32f-4446-aacc-bf861690f6ee"]
+pub struct Ruleset {
+    pub name: String,
+    pub species: Vec<SpeciesRules>,
+    // (predator, prey) pairs of species names
+    pub beats: Vec<(String, String)>,
+}
+
+#[derive(Deserialize)]
+pub struct SpeciesRules {
+    pub name: String,
+    // relative to the assets folder
+    pub sprite: String,
+    // which way the sprite's art points, in degrees counterclockwise from the right
+    #[serde(default = "default_sprite_forward")]
+    pub forward: f32,
+    // optional animated version of `sprite`, one row per clip (idle, move, flee, chase,
+    // converting) with `frames` frames each
+    #[serde(default)]
+    pub sheet: Option<String>,
+    #[serde(default = "default_frames")]
+    pub frames: usize,
+    // optional sound played when it catches something, relative to the assets folder
+    #[serde(default)]
+    pub sound: Option<String>,
+    // starting count, still editable from the main menu
+    pub count: u16,
+    // top speed, in units per second
+    #[serde(default = "default_speed")]
+    pub speed: f32,
+    // how hard it can steer, in units per second, per second
+    #[serde(default = "default_force")]
+    pub force: f32,
+    // how fast it can turn, in degrees per second
+    #[serde(default = "default_turn_rate")]
+    pub turn_rate: f32,
+    // how strongly nearby prey and predators sway it, compared to other species
+    #[serde(default = "default_weight")]
+    pub bravery: f32,
+    #[serde(default = "default_weight")]
+    pub caution: f32,
+    // how far it can see, and how wide its view is in degrees
+    #[serde(default = "default_perception")]
+    pub perception: f32,
+    #[serde(default = "default_fov")]
+    pub fov: f32,
+}
+
+fn default_sprite_forward() -> f32 {
+    DEFAULT_SPRITE_FORWARD
+}
+
+fn default_frames() -> usize {
+    DEFAULT_SHEET_FRAMES
+}
+
+fn default_speed() -> f32 {
+    ENTITY_MAX_SPEED
+}
+
+fn default_force() -> f32 {
+    ENTITY_MAX_FORCE
+}
+
+fn default_turn_rate() -> f32 {
+    ENTITY_MAX_TURN_RATE
+}
+
+fn default_weight() -> f32 {
+    1.0
+}
+
+fn default_perception() -> f32 {
+    PERCEPTION_RADIUS
+}
+
+fn default_fov() -> f32 {
+    FIELD_OF_VIEW_DEGREES
+}
+
+// plain rock paper scissors, built in so there is always something to play
+// even when the ruleset files are missing or broken
+impl Default for Ruleset {
+    fn default() -> Self {
+        let species = |name: &str| SpeciesRules {
+            name: name.to_string(),
+            sprite: format!("sprites/{}.png", name),
+            forward: DEFAULT_SPRITE_FORWARD,
+            sheet: Some(format!("sprites/{}_sheet.png", name)),
+            frames: DEFAULT_SHEET_FRAMES,
+            sound: None,
+            count: DEFAULT_ENTITY_COUNT,
+            speed: ENTITY_MAX_SPEED,
+            force: ENTITY_MAX_FORCE,
+            turn_rate: ENTITY_MAX_TURN_RATE,
+            bravery: 1.0,
+            caution: 1.0,
+            perception: PERCEPTION_RADIUS,
+            fov: FIELD_OF_VIEW_DEGREES,
+        };
+        let beats = |predator: &str, prey: &str| (predator.to_string(), prey.to_string());
+
+        Self {
+            name: "rock paper scissors".to_string(),
+            species: vec![species("rock"), species("paper"), species("scissors")],
+            beats: vec![
+                beats("rock", "scissors"),
+                beats("paper", "rock"),
+                beats("scissors", "paper"),
+            ],
+        }
+    }
+}
+
+impl Ruleset {
+    pub fn parse(bytes: &[u8]) -> Result<Self, String> {
+        // lets optional fields like `sheet` be written without wrapping them in `Some(...)`
+        let ruleset: Ruleset = ron::Options::default()
+            .with_default_extension(Extensions::IMPLICIT_SOME)
+            .from_bytes(bytes)
+            .map_err(|e| e.to_string())?;
+        // catch typos in `beats` and broken numbers here rather than mid-match
+        ruleset.check_species()?;
+        FoodChain::from_ruleset(&ruleset)?;
+        Ok(ruleset)
+    }
+
+    fn check_species(&self) -> Result<(), String> {
+        // `Species` is a u8, and `FoodChain::species` counts up to the length as one too
+        if self.species.len() > u8::MAX as usize {
+            return Err(format!(
+                "a food chain can have at most {} species, not {}",
+                u8::MAX,
+                self.species.len()
+            ));
+        }
+        for rules in &self.species {
+            // also rules out NaN and infinity
+            let in_range = |field: &str, value: f32, max: f32| {
+                if (0.0..=max).contains(&value) {
+                    Ok(())
+                } else {
+                    Err(format!(
+                        "{}'s {} is {}, it has to be between 0 and {}",
+                        rules.name, field, value, max
+                    ))
+                }
+            };
+            in_range("speed", rules.speed, MAX_SPEED)?;
+            in_range("force", rules.force, f32::MAX)?;
+            in_range("turn_rate", rules.turn_rate, f32::MAX)?;
+            in_range("bravery", rules.bravery, f32::MAX)?;
+            in_range("caution", rules.caution, f32::MAX)?;
+            in_range("perception", rules.perception, MAX_PERCEPTION_RADIUS)?;
+            in_range("fov", rules.fov, 360.0)?;
+            if !rules.forward.is_finite() {
+                return Err(format!("{}'s forward is {}", rules.name, rules.forward));
+            }
+            if rules.frames > MAX_SHEET_FRAMES {
+                return Err(format!(
+                    "{}'s sheet has {} frames, it can have at most {}",
+                    rules.name, rules.frames, MAX_SHEET_FRAMES
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    // headless runs have no asset server, so read the file directly
+    pub fn from_file(name: &str) -> Result<Self, String> {
+        let path = FileAssetIo::get_base_path()
+            .join("assets")
+            .join(ruleset_path(name));
+        let bytes = fs::read(&path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        Self::parse(&bytes)
+    }
+}
+
+pub fn ruleset_path(name: &str) -> String {
+    format!("rulesets/{}.ron", name)
+}
+
+#[derive(Default)]
+pub struct RulesetLoader;
+
+impl AssetLoader for RulesetLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), bevy::asset::Error>> {
+        Box::pin(async move {
+            let ruleset = Ruleset::parse(bytes).map_err(bevy::asset::Error::msg)?;
+            load_context.set_default_asset(LoadedAsset::new(ruleset));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["ron"]
+    }
+}
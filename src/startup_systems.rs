@@ -1,17 +1,39 @@
-use bevy::{prelude::*, window::PrimaryWindow};
+use bevy::{prelude::*, render::camera::ScalingMode};
+use rand::prelude::*;
 
-use crate::components::{Angle, AssociatedString, IsInFoodChain, Paper, Rock, Scissors, Velocity};
-use crate::utils::{generate_exclusive_transform, ENTITY_COUNT};
+use crate::components::{
+    Angle, AngularVelocity, Behavior, IsInFoodChain, Lineage, MaxTurnRate, Perception, Position,
+    PreviousPosition, Species, SpriteAnimation, Velocity, Wander,
+};
+use crate::resources::{
+    Arena, FoodChain, GridEntry, RulesetHandles, SimulationClock, SimulationConfig,
+    SimulationResult, SimulationRng, SimulationSeed, SpatialGrid, SpeciesAssets, SpriteHandles,
+};
+use crate::ruleset::Ruleset;
+use crate::utils::{generate_exclusive_transform, ENTITY_SIZE, FONT_PATH};
 
-pub fn spawn_camera(mut commands: Commands, window_query: Query<&Window, With<PrimaryWindow>>) {
-    let window = window_query
-        .get_single()
-        .expect("There is no window, how is this possible?");
+pub fn load_rulesets(mut commands: Commands, asset_server: Res<AssetServer>) {
+    match asset_server.load_folder("rulesets") {
+        Ok(handles) => commands.insert_resource(RulesetHandles(
+            handles.into_iter().map(|handle| handle.typed()).collect(),
+        )),
+        Err(e) => eprintln!("couldn't load rulesets: {}", e),
+    }
+}
 
-    commands.spawn(Camera2dBundle {
-        transform: Transform::from_xyz(window.width() / 2.0, window.height() / 2.0, 7.0),
+// keeps the whole arena in view, letterboxing whichever axis has room to spare
+pub fn spawn_camera(mut commands: Commands, arena: Res<Arena>) {
+    let center = arena.center();
+    let mut camera = Camera2dBundle {
+        transform: Transform::from_xyz(center.x, center.y, 7.0),
         ..default()
-    });
+    };
+    camera.projection.scaling_mode = ScalingMode::AutoMin {
+        min_width: arena.width,
+        min_height: arena.height,
+    };
+
+    commands.spawn(camera);
 }
 
 pub fn despawn_entities(
@@ -23,49 +45,137 @@ pub fn despawn_entities(
     }
 }
 
-pub fn spawn_entities(
-    mut commands: Commands,
-    window_query: Query<&Window, With<PrimaryWindow>>,
-    asset_server: Res<AssetServer>,
+pub fn seed_simulation_rng(
+    simulation_seed: Res<SimulationSeed>,
+    mut simulation_rng: ResMut<SimulationRng>,
 ) {
-    let window = window_query
-        .get_single()
-        .expect("There is no window, how is this possible?");
+    let seed = simulation_seed.0.unwrap_or_else(random);
+    *simulation_rng = SimulationRng::new(seed);
+    println!("seed: {}", seed);
+}
 
-    let mut taken_positions: Vec<Vec3> = vec![];
+pub fn reset_simulation_clock(mut commands: Commands) {
+    commands.insert_resource(SimulationClock::default());
+    commands.remove_resource::<SimulationResult>();
+}
 
-    let texture = asset_server.load(format!("sprites/{}.png", Rock::STRING));
-    for _ in 0..ENTITY_COUNT {
-        spawn_entity::<Rock>(&mut commands, window, texture.clone(), &mut taken_positions);
-    }
+pub fn load_font(mut species_assets: ResMut<SpeciesAssets>, asset_server: Res<AssetServer>) {
+    species_assets.font = asset_server.load(FONT_PATH);
+}
 
-    let texture = asset_server.load(format!("sprites/{}.png", Paper::STRING));
-    for _ in 0..ENTITY_COUNT {
-        spawn_entity::<Paper>(&mut commands, window, texture.clone(), &mut taken_positions);
+// starts loading the sprite sheet and capture sound of every species in every ruleset loaded
+// so far, plus the food chain in play. each file is only loaded and cut into an atlas once
+pub fn load_species_assets(
+    mut species_assets: ResMut<SpeciesAssets>,
+    food_chain: Res<FoodChain>,
+    rulesets: Res<Assets<Ruleset>>,
+    asset_server: Res<AssetServer>,
+    mut texture_atlases: ResMut<Assets<TextureAtlas>>,
+) {
+    let food_chains = rulesets
+        .iter()
+        .filter_map(|(_, ruleset)| FoodChain::from_ruleset(ruleset).ok())
+        .chain(std::iter::once(food_chain.clone()));
+    for food_chain in food_chains {
+        for species in food_chain.species() {
+            let (path, columns, rows) = food_chain.sprite_sheet(species);
+            if !species_assets.sprites.contains_key(path) {
+                let texture: Handle<Image> = asset_server.load(path);
+                let atlas = texture_atlases.add(TextureAtlas::from_grid(
+                    texture.clone(),
+                    Vec2::splat(ENTITY_SIZE),
+                    columns,
+                    rows,
+                    None,
+                    None,
+                ));
+                species_assets.sprites.insert(
+                    path.to_string(),
+                    SpriteHandles {
+                        texture,
+                        atlas,
+                        columns,
+                        rows,
+                    },
+                );
+            }
+            if let Some(path) = food_chain.capture_sound(species) {
+                if !species_assets.capture_sounds.contains_key(path) {
+                    let sound = asset_server.load(path);
+                    species_assets
+                        .capture_sounds
+                        .insert(path.to_string(), sound);
+                }
+            }
+        }
     }
+}
+
+pub fn spawn_entities(
+    mut commands: Commands,
+    arena: Res<Arena>,
+    food_chain: Res<FoodChain>,
+    simulation_config: Res<SimulationConfig>,
+    species_assets: Res<SpeciesAssets>,
+    mut simulation_rng: ResMut<SimulationRng>,
+    mut spatial_grid: ResMut<SpatialGrid>,
+) {
+    let rng = &mut simulation_rng.rng;
 
-    let texture = asset_server.load(format!("sprites/{}.png", Scissors::STRING));
-    for _ in 0..ENTITY_COUNT {
-        spawn_entity::<Scissors>(&mut commands, window, texture.clone(), &mut taken_positions);
+    // keeps track of who is already placed, so finding a free spot stays quick with thousands
+    spatial_grid.rebuild(&arena, std::iter::empty());
+
+    for species in food_chain.species() {
+        let texture_atlas = species_assets.atlas(&food_chain, species);
+        for _ in 0..simulation_config.count(species) {
+            spawn_entity(
+                &mut commands,
+                species,
+                food_chain.perception(species),
+                food_chain.max_turn_rate(species),
+                &arena,
+                rng,
+                texture_atlas.clone(),
+                &mut spatial_grid,
+            );
+        }
     }
 }
 
-pub fn spawn_entity<T: Component + Default>(
+pub fn spawn_entity(
     commands: &mut Commands,
-    window: &Window,
-    texture: Handle<Image>,
-    taken_positions: &mut Vec<Vec3>,
+    species: Species,
+    perception: Perception,
+    max_turn_rate: MaxTurnRate,
+    arena: &Arena,
+    rng: &mut impl Rng,
+    texture_atlas: Handle<TextureAtlas>,
+    taken_positions: &mut SpatialGrid,
 ) {
-    let transform = generate_exclusive_transform(window, taken_positions);
-    commands.spawn((
-        SpriteBundle {
+    let transform = generate_exclusive_transform(arena, rng, taken_positions);
+    let entity = commands.spawn((
+        SpriteSheetBundle {
             transform,
-            texture,
+            texture_atlas,
             ..default()
         },
-        T::default(),
+        SpriteAnimation::default(),
+        Behavior::Wandering,
+        species,
+        Lineage::new(species),
+        perception,
         IsInFoodChain,
+        Position(transform.translation),
+        PreviousPosition(transform.translation),
         Velocity(Vec3::ZERO),
-        Angle::new(),
+        Angle::new(rng),
+        AngularVelocity(0.0),
+        max_turn_rate,
+        Wander::new(rng),
     ));
+    taken_positions.insert(GridEntry {
+        entity: entity.id(),
+        species,
+        position: transform.translation,
+    });
 }
@@ -0,0 +1,73 @@
+use std::f32::consts::{PI, TAU};
+
+use bevy::prelude::*;
+
+use crate::utils::{WANDER_DISTANCE, WANDER_RADIUS};
+
+// Reynolds style steering behaviours. each one returns the change in velocity it wants,
+// callers add them up and limit the total with `limit_force`.
+// trigonometry goes through libm so a seed plays out the same on every platform
+
+// full speed straight at the target
+pub fn seek(to_target: Vec3, velocity: Vec3, max_speed: f32) -> Vec3 {
+    to_target.normalize_or_zero() * max_speed - velocity
+}
+
+// full speed straight away from the threat
+pub fn flee(to_threat: Vec3, velocity: Vec3, max_speed: f32) -> Vec3 {
+    -seek(to_threat, -velocity, max_speed)
+}
+
+// like `seek`, but eases off inside `slowing_radius` instead of overshooting
+pub fn arrive(to_target: Vec3, velocity: Vec3, max_speed: f32, slowing_radius: f32) -> Vec3 {
+    let distance = to_target.length();
+    let speed = max_speed * (distance / slowing_radius).min(1.0);
+    to_target.normalize_or_zero() * speed - velocity
+}
+
+// heads for a point on a circle out in front, for when there is nothing to chase or run from.
+// nudging `wander_angle` a little every tick makes for smooth, aimless turns
+pub fn wander(heading: Vec3, wander_angle: f32, velocity: Vec3, speed: f32) -> Vec3 {
+    let center = heading.normalize_or_zero() * WANDER_DISTANCE;
+    let displacement = Vec3::new(libm::cosf(wander_angle), libm::sinf(wander_angle), 0.0);
+    seek(center + displacement * WANDER_RADIUS, velocity, speed)
+}
+
+// the weighted average of several behaviours, or nothing if none of them carry any weight
+pub fn blend(weighted_forces: impl Iterator<Item = (Vec3, f32)>) -> Option<Vec3> {
+    let (total, weights) = weighted_forces
+        .fold((Vec3::ZERO, 0.0), |(total, weights), (force, weight)| {
+            (total + force * weight, weights + weight)
+        });
+    (weights > 0.0).then(|| total / weights)
+}
+
+// 1 right on top of something, dropping off smoothly to 0 at `radius`
+pub fn falloff(distance: f32, radius: f32) -> f32 {
+    let closeness = (1.0 - distance / radius).max(0.0);
+    closeness * closeness
+}
+
+// the shortest way round from one heading to another, in radians between -PI and PI
+pub fn heading_error(current: f32, desired: f32) -> f32 {
+    wrap_angle(desired - current)
+}
+
+pub fn wrap_angle(angle: f32) -> f32 {
+    (angle + PI).rem_euclid(TAU) - PI
+}
+
+// how much of its top speed an entity keeps while turning,
+// all of it when facing the right way and none when facing the opposite way
+pub fn turning_speed_factor(heading_error: f32) -> f32 {
+    1.0 - heading_error.abs() / PI
+}
+
+pub fn limit_force(force: Vec3, max_force: f32) -> Vec3 {
+    force.clamp_length_max(max_force)
+}
+
+// applies a steering force for one tick, never going faster than `max_speed`
+pub fn apply_force(velocity: Vec3, force: Vec3, max_speed: f32, delta_seconds: f32) -> Vec3 {
+    (velocity + force * delta_seconds).clamp_length_max(max_speed)
+}
@@ -1,278 +1,1237 @@
-use bevy::transform::commands;
-use bevy::{prelude::*, window::PrimaryWindow};
+use std::f32::consts::PI;
+
+use bevy::{app::AppExit, asset::LoadState, prelude::*, utils::HashMap};
+use rand::prelude::*;
 
 use crate::components::{
-    Angle, AssociatedString, IsInFoodChain, MainMenu, Paper, PauseButton, PlayButton, Rock,
-    Scissors, SimulationOverTimer, SimulationPage, Velocity,
+    Angle, AngularVelocity, Behavior, BoundaryButton, Clip, Conversion, Converting, CountButton,
+    CountSpinners, CountText, CountdownText, EndConditionButton, IsInFoodChain, Lineage, MainMenu,
+    MaxTurnRate, PauseButton, Perception, PlayAgainButton, PlayButton, Position, PreviousPosition,
+    RulesetButton, SimulationControls, SimulationOverTimer, SimulationPage, Species, SpeedButton,
+    SpriteAnimation, Velocity, Wander,
+};
+use crate::events::SimulationEnded;
+use crate::resources::{
+    Arena, BoundaryMode, EndCondition, FoodChain, GridEntry, LineageStats, RulesetHandles,
+    SimulationClock, SimulationConfig, SimulationResult, SimulationRng, SimulationSpeed,
+    SpatialGrid, SpeciesAssets,
+};
+use crate::ruleset::Ruleset;
+use crate::steering::{
+    apply_force, arrive, blend, falloff, flee, heading_error, limit_force, turning_speed_factor,
+    wander, wrap_angle,
 };
 use crate::{utils::*, AppState, PlayState};
 
-pub fn entity_movement<O: Component, H: Component, L: Component>(
-    mut own_query: Query<(&mut Transform, &mut Velocity, &mut Angle), With<O>>,
-    predators_query: Query<&Transform, (With<H>, Without<O>)>,
-    prey_query: Query<&Transform, (With<L>, Without<O>)>,
+// snapshot of where everything was before this tick moves it, for render interpolation
+pub fn store_previous_positions(
+    mut entities_query: Query<(&Position, &mut PreviousPosition), With<IsInFoodChain>>,
+) {
+    for (position, mut previous_position) in entities_query.iter_mut() {
+        previous_position.0 = position.0;
+    }
+}
+
+// indexes where everything is at the start of the tick, for the neighbour lookups after it.
+// entities busy converting are left out, nothing can chase, flee or catch them meanwhile
+pub fn rebuild_spatial_grid(
+    entities_query: Query<(Entity, &Species, &Position), Without<Converting>>,
+    arena: Res<Arena>,
+    mut spatial_grid: ResMut<SpatialGrid>,
+) {
+    spatial_grid.rebuild(
+        &arena,
+        entities_query
+            .iter()
+            .map(|(entity, species, position)| GridEntry {
+                entity,
+                species: *species,
+                position: position.0,
+            }),
+    );
+}
+
+// extra search distance to cover how far entities move between the grid being built and being used:
+// a step at top speed when integrating, and about as much again when being pushed apart
+fn spatial_grid_slack(food_chain: &FoodChain, fixed_time: &FixedTime) -> f32 {
+    2.0 * food_chain.fastest_speed() * fixed_time.period.as_secs_f32()
+}
+
+// steering only decides on a new velocity, positions are left for `integrate_velocity`.
+// the food chain decides who is a predator and who is prey for each entity
+pub fn entity_movement(
+    mut own_query: Query<
+        (
+            &Species,
+            &Position,
+            &mut Velocity,
+            &mut Angle,
+            &mut AngularVelocity,
+            &MaxTurnRate,
+            &Perception,
+            &mut Wander,
+            &mut Behavior,
+        ),
+        Without<Converting>,
+    >,
+    arena: Res<Arena>,
+    food_chain: Res<FoodChain>,
+    spatial_grid: Res<SpatialGrid>,
+    fixed_time: Res<FixedTime>,
+    mut simulation_rng: ResMut<SimulationRng>,
+) {
+    let delta_seconds = fixed_time.period.as_secs_f32();
+    let rng = &mut simulation_rng.rng;
+
+    for (
+        species,
+        position,
+        mut velocity,
+        mut angle,
+        mut angular_velocity,
+        max_turn_rate,
+        perception,
+        mut wandering,
+        mut behavior,
+    ) in own_query.iter_mut()
+    {
+        let max_speed = food_chain.max_speed(*species);
+        let max_force = food_chain.max_force(*species);
+        let facing = angle.facing();
+
+        // weigh up everything in view, and just wander on when nothing is
+        let (mut force, new_behavior) = get_influenced_force(
+            *species,
+            position.0,
+            velocity.0,
+            facing,
+            perception,
+            &arena,
+            &food_chain,
+            &spatial_grid,
+        )
+        .unwrap_or_else(|| {
+            wandering.0 += rng.gen_range(-1.0..=1.0) * WANDER_JITTER * delta_seconds;
+            let heading = velocity.0.try_normalize().unwrap_or(facing);
+            let force = wander(heading, wandering.0, velocity.0, max_speed * WANDER_SPEED);
+            (force, Behavior::Wandering)
+        });
+        if *behavior != new_behavior {
+            *behavior = new_behavior;
+        }
+
+        if arena.boundary == BoundaryMode::Wall {
+            force += wall_avoidance(&arena, position.0) * max_force * 2.0;
+        }
+        let force = limit_force(force, max_force);
+
+        // turn the shortest way towards where steering wants to go, no faster than allowed.
+        // an entity not facing where it wants to go is slowed down, to a stop when facing
+        // the opposite way
+        let desired = velocity.0 + force;
+        let error = if desired.length_squared() > f32::EPSILON {
+            heading_error(angle.0, libm::atan2f(desired.y, desired.x))
+        } else {
+            0.0
+        };
+        angular_velocity.0 = (error * TURN_RESPONSIVENESS).clamp(-max_turn_rate.0, max_turn_rate.0);
+        angle.0 = wrap_angle(angle.0 + angular_velocity.0 * delta_seconds);
+
+        let max_speed = max_speed * turning_speed_factor(error);
+        velocity.0 = apply_force(velocity.0, force, max_speed, delta_seconds);
+    }
+}
+
+pub fn advance_simulation_clock(
+    mut simulation_clock: ResMut<SimulationClock>,
+    fixed_time: Res<FixedTime>,
+) {
+    simulation_clock.0 += fixed_time.period;
+}
+
+pub fn integrate_velocity(
+    mut entities_query: Query<
+        (&mut Position, &Velocity),
+        (With<IsInFoodChain>, Without<Converting>),
+    >,
+    fixed_time: Res<FixedTime>,
+) {
+    let delta_seconds = fixed_time.period.as_secs_f32();
+
+    for (mut position, velocity) in entities_query.iter_mut() {
+        position.0 += velocity.0 * delta_seconds;
+    }
+}
+
+// blends fleeing every predator and chasing every prey the entity can see,
+// weighted by how close each one is and how cautious or brave the species is.
+// whichever side carries more weight decides whether it counts as fleeing or chasing
+fn get_influenced_force(
+    species: Species,
+    position: Vec3,
+    velocity: Vec3,
+    facing: Vec3,
+    perception: &Perception,
+    arena: &Arena,
+    food_chain: &FoodChain,
+    spatial_grid: &SpatialGrid,
+) -> Option<(Vec3, Behavior)> {
+    let max_speed = food_chain.max_speed(species);
+    let bravery = food_chain.bravery(species);
+    let caution = food_chain.caution(species);
+
+    let influences: Vec<(Vec3, f32, Behavior)> = spatial_grid
+        .nearby(position, perception.radius)
+        .filter_map(|other| {
+            let offset = arena.offset(position, other.position);
+            let closeness = falloff(offset.length(), perception.radius);
+            if closeness <= 0.0 || !perception.can_see(facing, offset) {
+                None
+            } else if food_chain.eats(other.species, species) {
+                let run = flee(offset, velocity, max_speed);
+                Some((run, caution * closeness, Behavior::Fleeing))
+            } else if food_chain.eats(species, other.species) {
+                let chase = arrive(offset, velocity, max_speed, ARRIVE_SLOWING_RADIUS);
+                Some((chase, bravery * closeness, Behavior::Chasing))
+            } else {
+                None
+            }
+        })
+        .collect();
+
+    let force = blend(
+        influences
+            .iter()
+            .map(|(force, weight, _)| (*force, *weight)),
+    )?;
+    let weight_of = |behavior: Behavior| -> f32 {
+        influences
+            .iter()
+            .filter(|(_, _, b)| *b == behavior)
+            .map(|(_, weight, _)| weight)
+            .sum()
+    };
+    let behavior = if weight_of(Behavior::Fleeing) > weight_of(Behavior::Chasing) {
+        Behavior::Fleeing
+    } else {
+        Behavior::Chasing
+    };
+    Some((force, behavior))
+}
+
+// pushes away from any solid wall closer than `WALL_AVOIDANCE_DISTANCE`, harder the closer it is
+fn wall_avoidance(arena: &Arena, position: Vec3) -> Vec3 {
+    let to_min = position.truncate() - arena.inner_min();
+    let to_max = arena.inner_max() - position.truncate();
+    let push = |distance: f32| (1.0 - distance / WALL_AVOIDANCE_DISTANCE).max(0.0);
+    Vec3::new(
+        push(to_min.x) - push(to_max.x),
+        push(to_min.y) - push(to_max.y),
+        0.0,
+    )
+}
+
+pub fn contain_entities(
+    mut entities_query: Query<
+        (&mut Position, &mut PreviousPosition, &mut Velocity),
+        With<IsInFoodChain>,
+    >,
+    arena: Res<Arena>,
+) {
+    match arena.boundary {
+        BoundaryMode::Wrap => wrap_entities(&mut entities_query, &arena),
+        BoundaryMode::Bounce | BoundaryMode::Wall => {
+            let min = arena.inner_min();
+            let max = arena.inner_max();
+            for (mut position, _, mut velocity) in entities_query.iter_mut() {
+                let e = &mut position.0;
+                let v = &mut velocity.0;
+                // bouncing turns the velocity around, a wall just stops it
+                let bounce = arena.boundary == BoundaryMode::Bounce;
+
+                if e.x < min.x {
+                    e.x = min.x;
+                    v.x = if bounce { v.x.abs() } else { v.x.max(0.0) };
+                } else if e.x > max.x {
+                    e.x = max.x;
+                    v.x = if bounce { -v.x.abs() } else { v.x.min(0.0) };
+                }
+
+                if e.y < min.y {
+                    e.y = min.y;
+                    v.y = if bounce { v.y.abs() } else { v.y.max(0.0) };
+                } else if e.y > max.y {
+                    e.y = max.y;
+                    v.y = if bounce { -v.y.abs() } else { v.y.min(0.0) };
+                }
+            }
+        }
+        BoundaryMode::Infinite => {}
+    }
+}
+
+// wrapping is a teleport, so the previous position follows along to stop
+// the sprite from being interpolated across the whole arena
+fn wrap_entities(
+    entities_query: &mut Query<
+        (&mut Position, &mut PreviousPosition, &mut Velocity),
+        With<IsInFoodChain>,
+    >,
+    arena: &Arena,
+) {
+    let Vec2 { x: x_min, y: y_min } = arena.wrap_min();
+    let Vec2 { x: x_max, y: y_max } = arena.wrap_min() + arena.wrap_size();
+    for (mut position, mut previous_position, _) in entities_query.iter_mut() {
+        let e = &mut position.0;
+        let before = *e;
+
+        if e.x < x_min {
+            e.x = x_max;
+        } else if e.x > x_max {
+            e.x = x_min;
+        }
+
+        if e.y < y_min {
+            e.y = y_max;
+        } else if e.y > y_max {
+            e.y = y_min;
+        }
+
+        if *e != before {
+            previous_position.0 = *e;
+        }
+    }
+}
+
+// in an infinite arena there is nothing to frame, so keep the crowd in view instead
+pub fn follow_crowd(
+    mut camera_query: Query<&mut Transform, With<Camera>>,
+    entities_query: Query<&Transform, (With<IsInFoodChain>, Without<Camera>)>,
+    arena: Res<Arena>,
     time: Res<Time>,
 ) {
-    let prey_positions: Vec<Vec3> = prey_query.iter().map(|t| t.translation).collect();
+    if arena.boundary != BoundaryMode::Infinite {
+        return;
+    }
+    let count = entities_query.iter().len();
+    if count == 0 {
+        return;
+    }
+    let centroid = entities_query
+        .iter()
+        .map(|transform| transform.translation.truncate())
+        .sum::<Vec2>()
+        / count as f32;
 
-    let predator_positions: Vec<Vec3> = predators_query.iter().map(|t| t.translation).collect();
+    let catch_up = 1.0 - (-CAMERA_FOLLOW_RATE * time.delta_seconds()).exp();
+    for mut transform in camera_query.iter_mut() {
+        let current = transform.translation.truncate();
+        let target = current.lerp(centroid, catch_up);
+        transform.translation.x = target.x;
+        transform.translation.y = target.y;
+    }
+}
 
-    for (mut transform, mut velocity, mut angle) in own_query.iter_mut() {
-        let direction = get_own_direction(
-            &transform,
-            predator_positions.clone(),
-            prey_positions.clone(),
-        );
+pub fn reset_camera(mut camera_query: Query<&mut Transform, With<Camera>>, arena: Res<Arena>) {
+    let center = arena.center();
+    for mut transform in camera_query.iter_mut() {
+        transform.translation.x = center.x;
+        transform.translation.y = center.y;
+    }
+}
+
+// an entity caught by one of its predators starts converting to that predator's species.
+// anything already converting is out of the game until it's done, as prey and as predator
+pub fn detect_collisions_from_predators(
+    mut commands: Commands,
+    entities_query: Query<(Entity, &Species, &Position), Without<Converting>>,
+    arena: Res<Arena>,
+    mut lineage_query: Query<&mut Lineage>,
+    food_chain: Res<FoodChain>,
+    spatial_grid: Res<SpatialGrid>,
+    simulation_clock: Res<SimulationClock>,
+    species_assets: Res<SpeciesAssets>,
+    audio: Option<Res<Audio>>,
+    fixed_time: Res<FixedTime>,
+) {
+    let slack = spatial_grid_slack(&food_chain, &fixed_time);
+    for (entity, species, position) in entities_query.iter() {
+        let translation = position.0;
+
+        // the grid is from the start of the tick, so only use it to find candidates
+        let closest_predator = spatial_grid
+            .nearby(translation, ENTITY_SIZE + slack)
+            .filter_map(|other| entities_query.get(other.entity).ok())
+            .map(|(other, other_species, other_position)| {
+                let distance = arena.distance(other_position.0, translation);
+                (other, other_species, distance)
+            })
+            .filter(|(_, other_species, _)| food_chain.eats(**other_species, *species))
+            .min_by(|(_, _, a), (_, _, b)| a.total_cmp(b));
 
-        if let Some(mut direction) = direction {
-            if direction.length() > 0.0 {
-                direction = direction.normalize();
+        if let Some((predator, predator_species, predator_distance)) = closest_predator {
+            if predator_distance < ENTITY_SIZE {
+                if let Ok(mut lineage) = lineage_query.get_mut(entity) {
+                    lineage.conversions.push(Conversion {
+                        captor: predator,
+                        at: simulation_clock.0,
+                        to: *predator_species,
+                    });
+                }
+                commands.entity(entity).insert(Converting {
+                    from: *species,
+                    to: *predator_species,
+                    timer: Timer::from_seconds(CONVERSION_DURATION, TimerMode::Once),
+                });
+                if let (Some(audio), Some(sound)) = (
+                    audio.as_deref(),
+                    species_assets.capture_sound(&food_chain, *predator_species),
+                ) {
+                    audio.play(sound);
+                }
             }
+        }
+    }
+}
 
-            // set new angle unless pointing at proper direction already
-            // if NOT pointed in the right direction, should incur a movement penalty
-            // where 180 difference is 0 movement and facing the right angle is full movement
+// once its conversion has played out an entity becomes its captor's species for real
+pub fn finish_conversions(
+    mut commands: Commands,
+    mut converting_query: Query<(Entity, &mut Converting, &mut Transform)>,
+    food_chain: Res<FoodChain>,
+    species_assets: Res<SpeciesAssets>,
+    fixed_time: Res<FixedTime>,
+) {
+    for (entity, mut converting, mut transform) in converting_query.iter_mut() {
+        if !converting.timer.tick(fixed_time.period).finished() {
+            continue;
+        }
+        let species = converting.to;
+        transform.scale = Vec3::ONE;
+        commands.entity(entity).remove::<Converting>().insert((
+            species,
+            food_chain.perception(species),
+            food_chain.max_turn_rate(species),
+            species_assets.atlas(&food_chain, species),
+        ));
+    }
+}
 
-            let angle_as_radians = angle.0 / 180.0 * std::f32::consts::PI;
-            let anglee = Vec3::new(angle.0.sin(), angle.0.cos(), 0.0);
-            let diff = direction.angle_between(anglee);
+// shrinks the caught entity, swaps in its captor's sprite halfway, then pops it back up
+pub fn animate_conversions(
+    mut converting_query: Query<(&Converting, &mut Transform, &mut Handle<TextureAtlas>)>,
+    food_chain: Res<FoodChain>,
+    species_assets: Res<SpeciesAssets>,
+) {
+    for (converting, mut transform, mut texture_atlas) in converting_query.iter_mut() {
+        let progress = converting.timer.percent();
+        let scale = if progress < 0.5 {
+            1.0 - progress
+        } else {
+            let grow = (progress - 0.5) * 2.0;
+            0.5 + grow * 0.5 + (grow * PI).sin() * CONVERSION_POP
+        };
+        transform.scale = Vec3::new(scale, scale, 1.0);
 
-            let accel_modifier = if diff < 0.5 {
-                angle.0 -= diff;
-                ENTITY_ACCELERATION
-            } else if diff >= 0.5 && diff < 1.5 {
-                angle.0 += 0.1;
-                ENTITY_ACCELERATION * -2.0
-            } else if diff >= 1.5 {
-                angle.0 -= 0.1;
-                ENTITY_ACCELERATION * -2.0
-            } else {
-                0.0
-            };
-            transform.rotation = Quat::from_rotation_z(angle.0);
-
-            velocity.0 += (direction * accel_modifier).clamp(
-                Vec3::new(-ENTITY_MAX_SPEED, -ENTITY_MAX_SPEED, 0.0),
-                Vec3::new(ENTITY_MAX_SPEED, ENTITY_MAX_SPEED, 0.0),
-            );
-            transform.translation += velocity.0 * (time.delta_seconds() * TIME_FACTOR);
+        let atlas = species_assets.atlas(&food_chain, converting.showing());
+        if *texture_atlas != atlas {
+            *texture_atlas = atlas;
         }
     }
 }
 
-pub fn get_own_direction(
-    transform: &Mut<Transform>,
-    predator_positions: Vec<Vec3>,
-    prey_positions: Vec<Vec3>,
-) -> Option<Vec3> {
-    let translation = transform.translation;
+// steps through the clip that matches what each entity is doing.
+// the converting clip follows the conversion instead, fading out the old species
+// then back in as the new one
+pub fn animate_sprites(
+    mut entities_query: Query<(
+        &Species,
+        &Velocity,
+        &Behavior,
+        Option<&Converting>,
+        &mut SpriteAnimation,
+        &mut TextureAtlasSprite,
+    )>,
+    food_chain: Res<FoodChain>,
+    species_assets: Res<SpeciesAssets>,
+    time: Res<Time>,
+) {
+    for (species, velocity, behavior, converting, mut animation, mut sprite) in
+        entities_query.iter_mut()
+    {
+        let clip = match (converting, behavior) {
+            (Some(_), _) => Clip::Converting,
+            (None, Behavior::Fleeing) => Clip::Flee,
+            (None, Behavior::Chasing) => Clip::Chase,
+            (None, Behavior::Wandering) if velocity.0.length() < MIN_FACING_SPEED => Clip::Idle,
+            (None, Behavior::Wandering) => Clip::Move,
+        };
+        if animation.clip != clip {
+            animation.clip = clip;
+            animation.frame = 0;
+            animation.timer.reset();
+        }
 
-    let closest_predator_position: Option<Vec3> = get_closest(translation, &predator_positions);
-    let closest_prey_position: Option<Vec3> = get_closest(translation, &prey_positions);
+        let showing = converting.map_or(*species, |converting| converting.showing());
+        let (columns, rows) = species_assets.layout(&food_chain, showing);
+        match converting {
+            Some(converting) => {
+                let progress = converting.timer.percent();
+                let fade = 1.0 - (progress * 2.0 - 1.0).abs();
+                animation.frame = ((fade * columns as f32) as usize).min(columns - 1);
+            }
+            None => {
+                animation.timer.tick(time.delta());
+                let frames = animation.timer.times_finished_this_tick() as usize;
+                animation.frame = (animation.frame + frames) % columns;
+            }
+        }
 
-    let run_away = if closest_predator_position.is_some() && closest_prey_position.is_some() {
-        closest_predator_position.unwrap().distance(translation)
-            < closest_prey_position.unwrap().distance(translation)
-    } else if closest_predator_position.is_some() {
-        true
-    } else if closest_prey_position.is_some() {
-        false
-    } else {
-        return None;
+        let index = clip.row().min(rows - 1) * columns + animation.frame.min(columns - 1);
+        if sprite.index != index {
+            sprite.index = index;
+        }
+    }
+}
+
+// TODO: this function name is fun but this whole thing
+//  could be folded into the movement system
+pub fn maintain_personal_space(
+    mut entity_query: Query<(Entity, &Species, &mut Position, &mut Velocity), Without<Converting>>,
+    arena: Res<Arena>,
+    food_chain: Res<FoodChain>,
+    spatial_grid: Res<SpatialGrid>,
+    fixed_time: Res<FixedTime>,
+) {
+    let delta_seconds = fixed_time.period.as_secs_f32();
+    let slack = spatial_grid_slack(&food_chain, &fixed_time);
+
+    // where everything handled so far ended up, each entity only moves away from those
+    let mut vv: HashMap<Entity, Vec3> = HashMap::default();
+    for (entity, species, mut current, mut velocity) in entity_query.iter_mut() {
+        let neighbours: Vec<Vec3> = spatial_grid
+            .nearby(current.0, ENTITY_SIZE + 5.0 + slack)
+            .filter(|other| other.species == *species)
+            .filter_map(|other| vv.get(&other.entity).copied())
+            .collect();
+        for existing_translation in &neighbours {
+            let away = arena.offset(*existing_translation, current.0);
+            if away.length() < (ENTITY_SIZE + 5.0) {
+                let direction = away.normalize_or_zero();
+
+                let max_speed = food_chain.max_speed(*species);
+                // push harder when avoiding same type of self
+                let force = direction * food_chain.max_force(*species) * 3.0;
+                velocity.0 = apply_force(velocity.0, force, max_speed, delta_seconds);
+                current.0 += velocity.0 * delta_seconds;
+            }
+        }
+        vv.insert(entity, current.0);
+    }
+}
+
+// blends between the last two ticks so movement looks smooth at any frame rate
+pub fn interpolate_transforms(
+    mut entities_query: Query<(&mut Transform, &Position, &PreviousPosition)>,
+    fixed_time: Res<FixedTime>,
+) {
+    let alpha = fixed_time.accumulated().as_secs_f32() / fixed_time.period.as_secs_f32();
+    for (mut transform, position, previous_position) in entities_query.iter_mut() {
+        transform.translation = previous_position.0.lerp(position.0, alpha);
+    }
+}
+
+// turns sprites towards where they are going, easing in so they don't snap around.
+// entities that have all but stopped keep whichever way they were facing
+pub fn face_velocity(
+    mut entities_query: Query<(&Species, &Velocity, &mut Transform)>,
+    food_chain: Res<FoodChain>,
+    time: Res<Time>,
+) {
+    let catch_up = 1.0 - (-SPRITE_TURN_RATE * time.delta_seconds()).exp();
+    for (species, velocity, mut transform) in entities_query.iter_mut() {
+        if velocity.0.length() < MIN_FACING_SPEED {
+            continue;
+        }
+        let heading = velocity.0.y.atan2(velocity.0.x);
+        let target = Quat::from_rotation_z(heading - food_chain.sprite_forward(*species));
+        transform.rotation = transform.rotation.slerp(target, catch_up);
+    }
+}
+
+// headless runs don't need to wait on the wall clock, so step the simulation
+// by a whole tick every frame on top of whatever real time has passed
+pub fn step_fixed_time(mut fixed_time: ResMut<FixedTime>) {
+    let period = fixed_time.period;
+    fixed_time.tick(period);
+}
+
+pub fn is_game_over(
+    species_query: Query<&Species>,
+    lineage_query: Query<(Entity, &Lineage)>,
+    mut commands: Commands,
+    mut next_game_state: ResMut<NextState<AppState>>,
+    mut simulation_ended_writer: EventWriter<SimulationEnded>,
+    food_chain: Res<FoodChain>,
+    simulation_clock: Res<SimulationClock>,
+    simulation_config: Res<SimulationConfig>,
+) {
+    let mut counts: Vec<(Species, usize)> = food_chain.species().map(|s| (s, 0)).collect();
+    for species in species_query.iter() {
+        if let Some((_, count)) = counts.get_mut(species.0 as usize) {
+            *count += 1;
+        }
+    }
+
+    let decided = match simulation_config.end_condition {
+        EndCondition::FirstExtinction => counts
+            .iter()
+            .any(|(_, count)| *count == 0)
+            .then(|| decided_winner(&counts, &food_chain)),
+        EndCondition::LastSpeciesStanding => {
+            let mut survivors = counts.iter().filter(|(_, count)| *count > 0);
+            let winner = survivors.next().map(|(species, _)| *species);
+            survivors.next().is_none().then_some(winner)
+        }
+        EndCondition::TimeLimit => {
+            (simulation_clock.0 >= simulation_config.time_limit).then(|| largest_species(&counts))
+        }
+        EndCondition::Sandbox => None,
     };
 
-    let direction = if run_away {
-        let closest_predator_position = closest_predator_position.unwrap();
-        Vec3::new(
-            if translation.x - closest_predator_position.x >= 0.0 {
-                1.0
-            } else {
-                -1.0
-            },
-            if translation.y - closest_predator_position.y >= 0.0 {
-                1.0
-            } else {
-                -1.0
-            },
-            0.0,
-        )
-    } else {
-        let closest_prey_position = closest_prey_position.unwrap();
-        Vec3::new(
-            if translation.x - closest_prey_position.x >= 0.0 {
-                -1.0
-            } else {
-                1.0
-            },
-            if translation.y - closest_prey_position.y >= 0.0 {
-                -1.0
-            } else {
-                1.0
-            },
-            0.0,
-        )
+    // a match the rule hasn't decided by `max_duration` goes to the largest species
+    let cut_off = decided.is_none();
+    let out_of_time = simulation_config
+        .max_duration
+        .is_some_and(|limit| simulation_clock.0 >= limit);
+    let Some(winner) = decided.or_else(|| out_of_time.then(|| largest_species(&counts))) else {
+        return;
     };
 
-    Some(direction)
+    let duration = simulation_clock.0;
+    let stats = LineageStats::new(lineage_query.iter(), &food_chain, duration);
+    println!(
+        "game over!!!! {} after {}{}",
+        describe_winner(winner, &food_chain),
+        format_duration(duration),
+        if cut_off { ", cut off" } else { "" }
+    );
+    for line in describe_lineage_stats(&stats, &food_chain) {
+        println!("{}", line);
+    }
+    simulation_ended_writer.send(SimulationEnded {
+        winner,
+        duration,
+        cut_off,
+    });
+    commands.insert_resource(SimulationResult {
+        winner,
+        duration,
+        end_condition: simulation_config.end_condition,
+        cut_off,
+        stats,
+    });
+    next_game_state.set(AppState::SimulationOver);
+}
+
+// once a species dies out, whoever is left with nothing to fear is bound to win.
+// in bigger food chains that may not be a single species, so fall back on the largest one
+fn decided_winner(counts: &[(Species, usize)], food_chain: &FoodChain) -> Option<Species> {
+    let survivors: Vec<Species> = counts
+        .iter()
+        .filter(|(_, count)| *count > 0)
+        .map(|(species, _)| *species)
+        .collect();
+    let mut fearless = survivors.iter().filter(|species| {
+        !survivors
+            .iter()
+            .any(|predator| food_chain.eats(*predator, **species))
+    });
+
+    match (fearless.next(), fearless.next()) {
+        (Some(winner), None) => Some(*winner),
+        _ => largest_species(counts),
+    }
 }
 
-pub fn get_closest(target: Vec3, positions: &Vec<Vec3>) -> Option<Vec3> {
-    positions.iter().fold(None, |acc, t| {
-        if let Some(existing) = acc {
-            if existing.distance(target) > t.distance(target) {
-                Some(*t)
+// a tie for the lead is a draw
+fn largest_species(counts: &[(Species, usize)]) -> Option<Species> {
+    let most = counts.iter().map(|(_, count)| *count).max().unwrap_or(0);
+    let mut leaders = counts.iter().filter(|(_, count)| *count == most);
+    match (leaders.next(), leaders.next()) {
+        (Some((winner, _)), None) => Some(*winner),
+        _ => None,
+    }
+}
+
+pub fn exit_on_simulation_end(
+    mut simulation_ended_reader: EventReader<SimulationEnded>,
+    mut app_exit_writer: EventWriter<AppExit>,
+    food_chain: Res<FoodChain>,
+    simulation_rng: Res<SimulationRng>,
+) {
+    if let Some(simulation_ended) = simulation_ended_reader.iter().next() {
+        println!(
+            "winner: {} after {} (seed {}){}",
+            simulation_ended
+                .winner
+                .map_or("none, it's a draw", |winner| food_chain.name(winner)),
+            format_duration(simulation_ended.duration),
+            simulation_rng.seed,
+            if simulation_ended.cut_off {
+                ", cut off by --max-duration"
             } else {
-                acc
+                ""
             }
-        } else {
-            Some(*t)
+        );
+        app_exit_writer.send(AppExit);
+    }
+}
+
+pub const MAIN_MENU_STYLE: Style = Style {
+    flex_direction: FlexDirection::Column,
+    justify_content: JustifyContent::Center,
+    align_items: AlignItems::Center,
+    size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+    gap: Size::new(Val::Percent(4.0), Val::Percent(4.0)),
+    ..Style::DEFAULT
+};
+
+pub fn spawn_main_menu(
+    mut commands: Commands,
+    species_assets: Res<SpeciesAssets>,
+    arena: Res<Arena>,
+    food_chain: Res<FoodChain>,
+    simulation_config: Res<SimulationConfig>,
+) {
+    commands
+        .spawn((
+            NodeBundle {
+                style: MAIN_MENU_STYLE,
+                ..default()
+            },
+            MainMenu,
+        ))
+        .with_children(|parent| {
+            // title
+            parent.spawn(TextBundle {
+                text: Text {
+                    sections: vec![TextSection::new(
+                        "Rock Paper Scissulator",
+                        TextStyle {
+                            font: species_assets.font.clone(),
+                            font_size: 64.0,
+                            color: Color::BLACK,
+                        },
+                    )],
+                    ..default()
+                },
+                ..default()
+            });
+            // starting counts
+            parent
+                .spawn((
+                    NodeBundle {
+                        style: Style {
+                            flex_direction: FlexDirection::Column,
+                            align_items: AlignItems::Center,
+                            gap: Size::new(Val::Px(8.0), Val::Px(8.0)),
+                            ..Style::DEFAULT
+                        },
+                        ..default()
+                    },
+                    CountSpinners,
+                ))
+                .with_children(|parent| {
+                    spawn_count_spinners(parent, &species_assets, &food_chain, &simulation_config);
+                });
+            // ruleset
+            parent
+                .spawn((
+                    ButtonBundle {
+                        style: Style {
+                            justify_content: JustifyContent::Center,
+                            align_items: AlignItems::Center,
+                            size: Size::new(Val::Px(600.0), Val::Px(50.0)),
+                            ..Style::DEFAULT
+                        },
+                        background_color: BackgroundColor(Color::RED),
+                        ..default()
+                    },
+                    RulesetButton,
+                ))
+                .with_children(|parent| {
+                    parent.spawn(TextBundle {
+                        text: Text::from_section(
+                            ruleset_message(&food_chain),
+                            TextStyle {
+                                font: species_assets.font.clone(),
+                                font_size: 32.0,
+                                color: Color::BLACK,
+                            },
+                        ),
+                        ..default()
+                    });
+                });
+            // end condition
+            parent
+                .spawn((
+                    ButtonBundle {
+                        style: Style {
+                            justify_content: JustifyContent::Center,
+                            align_items: AlignItems::Center,
+                            size: Size::new(Val::Px(400.0), Val::Px(50.0)),
+                            ..Style::DEFAULT
+                        },
+                        background_color: BackgroundColor(Color::RED),
+                        ..default()
+                    },
+                    EndConditionButton,
+                ))
+                .with_children(|parent| {
+                    parent.spawn(TextBundle {
+                        text: Text::from_section(
+                            end_condition_message(&simulation_config),
+                            TextStyle {
+                                font: species_assets.font.clone(),
+                                font_size: 32.0,
+                                color: Color::BLACK,
+                            },
+                        ),
+                        ..default()
+                    });
+                });
+            // boundary
+            parent
+                .spawn((
+                    ButtonBundle {
+                        style: Style {
+                            justify_content: JustifyContent::Center,
+                            align_items: AlignItems::Center,
+                            size: Size::new(Val::Px(400.0), Val::Px(50.0)),
+                            ..Style::DEFAULT
+                        },
+                        background_color: BackgroundColor(Color::RED),
+                        ..default()
+                    },
+                    BoundaryButton,
+                ))
+                .with_children(|parent| {
+                    parent.spawn(TextBundle {
+                        text: Text::from_section(
+                            boundary_message(&arena),
+                            TextStyle {
+                                font: species_assets.font.clone(),
+                                font_size: 32.0,
+                                color: Color::BLACK,
+                            },
+                        ),
+                        ..default()
+                    });
+                });
+            // play button
+            parent
+                .spawn((
+                    ButtonBundle {
+                        style: Style {
+                            justify_content: JustifyContent::Center,
+                            align_items: AlignItems::Center,
+                            size: Size::new(Val::Px(200.0), Val::Px(80.0)),
+                            ..Style::DEFAULT
+                        },
+                        background_color: BackgroundColor(Color::RED),
+                        ..default()
+                    },
+                    PlayButton,
+                ))
+                .with_children(|parent| {
+                    parent.spawn(TextBundle {
+                        text: Text {
+                            sections: vec![TextSection::new(
+                                "Play",
+                                TextStyle {
+                                    font: species_assets.font.clone(),
+                                    font_size: 64.0,
+                                    color: Color::BLACK,
+                                },
+                            )],
+                            ..default()
+                        },
+                        ..default()
+                    });
+                });
+        });
+}
+
+fn spawn_count_spinners(
+    parent: &mut ChildBuilder,
+    species_assets: &SpeciesAssets,
+    food_chain: &FoodChain,
+    simulation_config: &SimulationConfig,
+) {
+    for species in food_chain.species() {
+        spawn_count_spinner(
+            parent,
+            species_assets,
+            species,
+            food_chain.name(species),
+            simulation_config.count(species),
+        );
+    }
+}
+
+pub fn update_ruleset_text(
+    button_query: Query<&Children, With<RulesetButton>>,
+    mut text_query: Query<&mut Text>,
+    food_chain: Res<FoodChain>,
+) {
+    if !food_chain.is_changed() {
+        return;
+    }
+    for children in button_query.iter() {
+        if let Ok(mut text) = text_query.get_mut(children[0]) {
+            text.sections[0].value = ruleset_message(&food_chain);
         }
-    })
+    }
 }
 
-pub fn contain_entities(
-    mut entities_query: Query<&mut Transform, With<IsInFoodChain>>,
-    window_query: Query<&Window, With<PrimaryWindow>>,
-) {
-    let window = window_query.get_single().unwrap();
-    let x_min = 0.0 - ENTITY_HALF_SIZE;
-    let x_max = window.width() + ENTITY_HALF_SIZE;
-    let y_min = 0.0 - ENTITY_HALF_SIZE;
-    let y_max = window.height() + ENTITY_HALF_SIZE;
-    for mut e in entities_query.iter_mut() {
-        if e.translation.x < x_min {
-            e.translation.x = x_max;
-        } else if e.translation.x > x_max {
-            e.translation.x = x_min;
+// shows the main menu once everything it and the first match need is in,
+// so nothing pops in a few frames late. files that fail to load are left out
+pub fn wait_for_assets(
+    asset_server: Res<AssetServer>,
+    species_assets: Res<SpeciesAssets>,
+    ruleset_handles: Res<RulesetHandles>,
+    rulesets: Res<Assets<Ruleset>>,
+    mut next_state: ResMut<NextState<AppState>>,
+) {
+    // a ruleset's species are only known, and so only loading, once the ruleset itself is in
+    let rulesets_read = ruleset_handles.0.iter().all(|handle| {
+        rulesets.contains(handle) || asset_server.get_load_state(handle) == LoadState::Failed
+    });
+    if !rulesets_read {
+        return;
+    }
+
+    let handles = species_assets
+        .handle_ids()
+        .chain(ruleset_handles.0.iter().map(|handle| handle.id()));
+    match asset_server.get_group_load_state(handles) {
+        LoadState::Loaded => next_state.set(AppState::MainMenu),
+        LoadState::Failed => {
+            eprintln!("some assets failed to load, carrying on without them");
+            next_state.set(AppState::MainMenu);
+        }
+        _ => {}
+    }
+}
+
+// picks up edits to the ruleset file currently in use while the game is running
+pub fn reload_ruleset(
+    mut ruleset_events: EventReader<AssetEvent<Ruleset>>,
+    asset_server: Res<AssetServer>,
+    rulesets: Res<Assets<Ruleset>>,
+    mut simulation_config: ResMut<SimulationConfig>,
+    mut food_chain: ResMut<FoodChain>,
+) {
+    for event in ruleset_events.iter() {
+        let AssetEvent::Modified { handle } = event else {
+            continue;
+        };
+        let (Some(name), Some(ruleset)) =
+            (ruleset_name(&asset_server, handle), rulesets.get(handle))
+        else {
+            continue;
+        };
+        if name != simulation_config.ruleset {
+            continue;
+        }
+        match simulation_config.use_ruleset(&name, ruleset, &mut food_chain) {
+            Ok(()) => println!("reloaded ruleset {}", name),
+            Err(e) => eprintln!("couldn't reload ruleset {}: {}", name, e),
+        }
+    }
+}
+
+// a different ruleset brings a different set of species to count
+pub fn rebuild_count_spinners(
+    mut commands: Commands,
+    spinners_query: Query<Entity, With<CountSpinners>>,
+    species_assets: Res<SpeciesAssets>,
+    food_chain: Res<FoodChain>,
+    simulation_config: Res<SimulationConfig>,
+) {
+    if !food_chain.is_changed() {
+        return;
+    }
+    for spinners in spinners_query.iter() {
+        let mut spinners = commands.entity(spinners);
+        spinners.despawn_descendants();
+        spinners.with_children(|parent| {
+            spawn_count_spinners(parent, &species_assets, &food_chain, &simulation_config);
+        });
+    }
+}
+
+// a row of "species  -10 -1 count +1 +10"
+fn spawn_count_spinner(
+    parent: &mut ChildBuilder,
+    species_assets: &SpeciesAssets,
+    species: Species,
+    name: &str,
+    count: u16,
+) {
+    let text_style = TextStyle {
+        font: species_assets.font.clone(),
+        font_size: 32.0,
+        color: Color::BLACK,
+    };
+
+    parent
+        .spawn(NodeBundle {
+            style: Style {
+                flex_direction: FlexDirection::Row,
+                align_items: AlignItems::Center,
+                gap: Size::new(Val::Px(8.0), Val::Px(8.0)),
+                ..Style::DEFAULT
+            },
+            ..default()
+        })
+        .with_children(|parent| {
+            parent.spawn(TextBundle {
+                style: Style {
+                    size: Size::new(Val::Px(120.0), Val::Auto),
+                    ..Style::DEFAULT
+                },
+                text: Text::from_section(name, text_style.clone()),
+                ..default()
+            });
+            for change in [-10, -1] {
+                spawn_count_button(parent, text_style.clone(), species, change);
+            }
+            parent.spawn((
+                TextBundle {
+                    style: Style {
+                        size: Size::new(Val::Px(80.0), Val::Auto),
+                        ..Style::DEFAULT
+                    },
+                    text: Text::from_section(count.to_string(), text_style.clone())
+                        .with_alignment(TextAlignment::Center),
+                    ..default()
+                },
+                CountText { species },
+            ));
+            for change in [1, 10] {
+                spawn_count_button(parent, text_style.clone(), species, change);
+            }
+        });
+}
+
+fn spawn_count_button(
+    parent: &mut ChildBuilder,
+    text_style: TextStyle,
+    species: Species,
+    change: i32,
+) {
+    parent
+        .spawn((
+            ButtonBundle {
+                style: Style {
+                    justify_content: JustifyContent::Center,
+                    align_items: AlignItems::Center,
+                    size: Size::new(Val::Px(60.0), Val::Px(40.0)),
+                    ..Style::DEFAULT
+                },
+                background_color: BackgroundColor(Color::RED),
+                ..default()
+            },
+            CountButton { species, change },
+        ))
+        .with_children(|parent| {
+            parent.spawn(TextBundle {
+                text: Text::from_section(format!("{:+}", change), text_style),
+                ..default()
+            });
+        });
+}
+
+pub fn count_button_interaction(
+    mut button_query: Query<
+        (&Interaction, &mut BackgroundColor, &CountButton),
+        Changed<Interaction>,
+    >,
+    mut simulation_config: ResMut<SimulationConfig>,
+) {
+    for (interaction, mut background_color, count_button) in button_query.iter_mut() {
+        match *interaction {
+            Interaction::Clicked => {
+                let count = simulation_config.count_mut(count_button.species);
+                *count =
+                    (*count as i32 + count_button.change).clamp(1, MAX_ENTITY_COUNT as i32) as u16;
+            }
+            Interaction::Hovered => {
+                *background_color = BackgroundColor(Color::BLUE);
+            }
+            Interaction::None => {
+                *background_color = BackgroundColor(Color::RED);
+            }
         }
+    }
+}
+
+pub fn update_count_text(
+    mut text_query: Query<(&mut Text, &CountText)>,
+    simulation_config: Res<SimulationConfig>,
+) {
+    if !simulation_config.is_changed() {
+        return;
+    }
 
-        if e.translation.y < y_min {
-            e.translation.y = y_max;
-        } else if e.translation.y > y_max {
-            e.translation.y = y_min;
-        }
+    for (mut text, count_text) in text_query.iter_mut() {
+        text.sections[0].value = simulation_config.count(count_text.species).to_string();
     }
 }
 
-pub fn detect_collisions_from_predators<O: Component, H: Component + Default + AssociatedString>(
-    mut commands: Commands,
-    mut own_query: Query<(&Transform, Entity), With<O>>,
-    predators_query: Query<&Transform, (With<H>, Without<O>)>,
+fn ruleset_message(food_chain: &FoodChain) -> String {
+    format!("rules: {}", food_chain.ruleset_name())
+}
+
+// cycles through the rulesets in `assets/rulesets` that have finished loading
+pub fn ruleset_button_interaction(
+    mut button_query: Query<
+        (&Interaction, &mut BackgroundColor),
+        (With<RulesetButton>, Changed<Interaction>),
+    >,
     asset_server: Res<AssetServer>,
+    ruleset_handles: Res<RulesetHandles>,
+    rulesets: Res<Assets<Ruleset>>,
+    mut simulation_config: ResMut<SimulationConfig>,
+    mut food_chain: ResMut<FoodChain>,
 ) {
-    let predator_positions: Vec<Vec3> = predators_query.iter().map(|t| t.translation).collect();
-
-    for (transform, entity) in own_query.iter_mut() {
-        let translation = transform.translation;
-
-        let closest_predator_position: Option<Vec3> = get_closest(translation, &predator_positions);
+    if let Ok((interaction, mut background_color)) = button_query.get_single_mut() {
+        match *interaction {
+            Interaction::Clicked => {
+                let mut available: Vec<(String, &Ruleset)> = ruleset_handles
+                    .0
+                    .iter()
+                    .filter_map(|handle| {
+                        Some((ruleset_name(&asset_server, handle)?, rulesets.get(handle)?))
+                    })
+                    .collect();
+                available.sort_by(|(a, _), (b, _)| a.cmp(b));
 
-        if let Some(closest_predator_position) = closest_predator_position {
-            if closest_predator_position.distance(translation) < ENTITY_SIZE {
-                commands
-                    .entity(entity)
-                    .remove::<O>()
-                    .remove::<SpriteBundle>()
-                    .insert((
-                        H::default(),
-                        SpriteBundle {
-                            transform: *transform,
-                            texture: asset_server.load(format!("sprites/{}.png", H::STRING)),
-                            ..default()
-                        },
-                    ));
+                let current = available
+                    .iter()
+                    .position(|(name, _)| *name == simulation_config.ruleset);
+                let next = current.map_or(0, |index| index + 1) % available.len().max(1);
+                if let Some((name, ruleset)) = available.get(next) {
+                    if let Err(e) = simulation_config.use_ruleset(name, ruleset, &mut food_chain) {
+                        eprintln!("couldn't switch to ruleset {}: {}", name, e);
+                    }
+                }
+            }
+            Interaction::Hovered => {
+                *background_color = BackgroundColor(Color::BLUE);
+            }
+            Interaction::None => {
+                *background_color = BackgroundColor(Color::RED);
             }
         }
     }
 }
 
-// TODO: this function name is fun but this whole thing
-//  could be folded into the movement system
-pub fn maintain_personal_space<T: Component>(
-    mut entity_query: Query<(&mut Transform, &mut Velocity), With<T>>,
-    time: Res<Time>,
-) {
-    let mut vv: Vec<Vec3> = vec![];
-    for (mut current, mut velocity) in entity_query.iter_mut() {
-        for existing_translation in &vv {
-            if existing_translation.distance(current.translation) < (ENTITY_SIZE + 5.0) {
-                let direction = Vec3::new(
-                    if current.translation.x - existing_translation.x >= 0.0 {
-                        1.0
-                    } else {
-                        -1.0
-                    },
-                    if current.translation.y - existing_translation.y >= 0.0 {
-                        1.0
-                    } else {
-                        -1.0
-                    },
-                    0.0,
-                );
+fn end_condition_message(simulation_config: &SimulationConfig) -> String {
+    match simulation_config.end_condition {
+        EndCondition::TimeLimit => format!(
+            "ends on: time limit ({})",
+            format_duration(simulation_config.time_limit)
+        ),
+        end_condition => format!("ends on: {}", end_condition),
+    }
+}
 
-                // accelerate faster when avoiding same type of self
-                velocity.0 += (direction * ENTITY_ACCELERATION * 3.0).clamp(
-                    Vec3::new(-ENTITY_MAX_SPEED, -ENTITY_MAX_SPEED, 0.0),
-                    Vec3::new(ENTITY_MAX_SPEED, ENTITY_MAX_SPEED, 0.0),
-                );
-                current.translation += velocity.0 * (time.delta_seconds() * TIME_FACTOR);
+// cycles through the end conditions
+pub fn end_condition_button_interaction(
+    mut button_query: Query<
+        (&Interaction, &mut BackgroundColor, &Children),
+        (With<EndConditionButton>, Changed<Interaction>),
+    >,
+    mut text_query: Query<&mut Text>,
+    mut simulation_config: ResMut<SimulationConfig>,
+) {
+    if let Ok((interaction, mut background_color, children)) = button_query.get_single_mut() {
+        match *interaction {
+            Interaction::Clicked => {
+                simulation_config.end_condition = simulation_config.end_condition.next();
+                if let Ok(mut text) = text_query.get_mut(children[0]) {
+                    text.sections[0].value = end_condition_message(&simulation_config);
+                }
+            }
+            Interaction::Hovered => {
+                *background_color = BackgroundColor(Color::BLUE);
+            }
+            Interaction::None => {
+                *background_color = BackgroundColor(Color::RED);
             }
         }
-        vv.push(current.translation.clone());
     }
 }
 
-pub fn is_game_over(
-    rocks_query: Query<&Rock>,
-    papers_query: Query<&Paper>,
-    scissors_query: Query<&Scissors>,
-    mut next_game_state: ResMut<NextState<AppState>>,
+fn boundary_message(arena: &Arena) -> String {
+    format!("edges: {}", arena.boundary)
+}
+
+// cycles through the boundary modes
+pub fn boundary_button_interaction(
+    mut button_query: Query<
+        (&Interaction, &mut BackgroundColor, &Children),
+        (With<BoundaryButton>, Changed<Interaction>),
+    >,
+    mut text_query: Query<&mut Text>,
+    mut arena: ResMut<Arena>,
 ) {
-    let no_rocks = rocks_query.is_empty();
-    let no_papers = papers_query.is_empty();
-    let no_scissors = scissors_query.is_empty();
-    let my_stuff: [bool; 3] = [no_rocks, no_papers, no_scissors];
-    if my_stuff.iter().any(|f| *f) {
-        println!("game over!!!!");
-        next_game_state.set(AppState::MainMenu);
+    if let Ok((interaction, mut background_color, children)) = button_query.get_single_mut() {
+        match *interaction {
+            Interaction::Clicked => {
+                arena.boundary = arena.boundary.next();
+                if let Ok(mut text) = text_query.get_mut(children[0]) {
+                    text.sections[0].value = boundary_message(&arena);
+                }
+            }
+            Interaction::Hovered => {
+                *background_color = BackgroundColor(Color::BLUE);
+            }
+            Interaction::None => {
+                *background_color = BackgroundColor(Color::RED);
+            }
+        }
     }
 }
 
-pub const MAIN_MENU_STYLE: Style = Style {
-    flex_direction: FlexDirection::Column,
-    justify_content: JustifyContent::Center,
-    align_items: AlignItems::Center,
-    size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
-    gap: Size::new(Val::Percent(8.0), Val::Percent(8.0)),
-    ..Style::DEFAULT
-};
+pub fn despawn_main_menu(mut commands: Commands, main_menu_query: Query<Entity, With<MainMenu>>) {
+    if let Ok(main_menu) = main_menu_query.get_single() {
+        commands.entity(main_menu).despawn_recursive();
+    }
+}
 
-pub fn spawn_main_menu(mut commands: Commands, asset_server: Res<AssetServer>) {
+// the play/pause toggle with the speed buttons lined up next to it
+pub fn spawn_simulation_controls(
+    mut commands: Commands,
+    species_assets: Res<SpeciesAssets>,
+    simulation_speed: Res<SimulationSpeed>,
+) {
     commands
         .spawn((
             NodeBundle {
-                style: MAIN_MENU_STYLE,
+                style: Style {
+                    flex_direction: FlexDirection::Row,
+                    gap: Size::new(Val::Px(8.0), Val::Px(8.0)),
+                    ..Style::DEFAULT
+                },
                 ..default()
             },
-            MainMenu,
+            SimulationControls,
         ))
         .with_children(|parent| {
-            // title
-            parent.spawn(TextBundle {
-                text: Text {
-                    sections: vec![TextSection::new(
-                        "Rock Paper Scissulator",
-                        TextStyle {
-                            font: asset_server.load("fonts/FiraSans-Regular.ttf"),
-                            font_size: 64.0,
-                            color: Color::BLACK,
-                        },
-                    )],
-                    ..default()
-                },
-                ..default()
-            });
-            // play button
             parent
                 .spawn((
                     ButtonBundle {
@@ -280,20 +1239,19 @@ pub fn spawn_main_menu(mut commands: Commands, asset_server: Res<AssetServer>) {
                             justify_content: JustifyContent::Center,
                             align_items: AlignItems::Center,
                             size: Size::new(Val::Px(200.0), Val::Px(80.0)),
-                            ..Style::DEFAULT
+                            ..Style::default()
                         },
-                        background_color: BackgroundColor(Color::RED),
                         ..default()
                     },
-                    PlayButton,
+                    PauseButton,
                 ))
                 .with_children(|parent| {
                     parent.spawn(TextBundle {
                         text: Text {
                             sections: vec![TextSection::new(
-                                "Play",
+                                "Play/Pause",
                                 TextStyle {
-                                    font: asset_server.load("fonts/FiraSans-Regular.ttf"),
+                                    font: species_assets.font.clone(),
                                     font_size: 64.0,
                                     color: Color::BLACK,
                                 },
@@ -303,56 +1261,150 @@ pub fn spawn_main_menu(mut commands: Commands, asset_server: Res<AssetServer>) {
                         ..default()
                     });
                 });
+
+            for speed in SIMULATION_SPEEDS {
+                parent
+                    .spawn((
+                        ButtonBundle {
+                            style: Style {
+                                justify_content: JustifyContent::Center,
+                                align_items: AlignItems::Center,
+                                size: Size::new(Val::Px(80.0), Val::Px(80.0)),
+                                ..Style::default()
+                            },
+                            background_color: speed_button_color(speed, simulation_speed.0),
+                            ..default()
+                        },
+                        SpeedButton { speed },
+                    ))
+                    .with_children(|parent| {
+                        parent.spawn(TextBundle {
+                            text: Text::from_section(
+                                format!("{}x", speed),
+                                TextStyle {
+                                    font: species_assets.font.clone(),
+                                    font_size: 32.0,
+                                    color: Color::BLACK,
+                                },
+                            ),
+                            ..default()
+                        });
+                    });
+            }
         });
 }
 
-pub fn despawn_main_menu(mut commands: Commands, main_menu_query: Query<Entity, With<MainMenu>>) {
-    if let Ok(main_menu) = main_menu_query.get_single() {
-        commands.entity(main_menu).despawn_recursive();
+pub fn despawn_simulation_controls(
+    mut commands: Commands,
+    simulation_controls_query: Query<Entity, With<SimulationControls>>,
+) {
+    if let Ok(simulation_controls) = simulation_controls_query.get_single() {
+        commands.entity(simulation_controls).despawn_recursive();
     }
 }
 
-pub fn spawn_play_toggle(mut commands: Commands, asset_server: Res<AssetServer>) {
-    commands
-        .spawn((
-            ButtonBundle {
-                style: Style {
-                    justify_content: JustifyContent::Center,
-                    align_items: AlignItems::Center,
-                    size: Size::new(Val::Px(200.0), Val::Px(80.0)),
-                    ..Style::default()
-                },
-                ..default()
-            },
-            PauseButton,
-        ))
-        .with_children(|parent| {
-            parent.spawn(TextBundle {
-                text: Text {
-                    sections: vec![TextSection::new(
-                        "Play/Pause",
-                        TextStyle {
-                            font: asset_server.load("fonts/FiraSans-Regular.ttf"),
-                            font_size: 64.0,
-                            color: Color::BLACK,
-                        },
-                    )],
-                    ..default()
-                },
-                ..default()
-            });
-        });
+fn speed_button_color(speed: f32, current_speed: f32) -> BackgroundColor {
+    if speed == current_speed {
+        BackgroundColor(Color::GREEN)
+    } else {
+        BackgroundColor(Color::RED)
+    }
 }
 
-pub fn despawn_play_toggle(
-    mut commands: Commands,
-    toggle_button_query: Query<Entity, With<PauseButton>>,
+pub fn speed_button_interaction(
+    mut button_query: Query<
+        (&Interaction, &mut BackgroundColor, &SpeedButton),
+        Changed<Interaction>,
+    >,
+    mut simulation_speed: ResMut<SimulationSpeed>,
+) {
+    for (interaction, mut background_color, speed_button) in button_query.iter_mut() {
+        match *interaction {
+            Interaction::Clicked => {
+                simulation_speed.0 = speed_button.speed;
+            }
+            Interaction::Hovered => {
+                *background_color = BackgroundColor(Color::BLUE);
+            }
+            Interaction::None => {
+                *background_color = speed_button_color(speed_button.speed, simulation_speed.0);
+            }
+        }
+    }
+}
+
+// keys 1 to 6 pick a speed directly, - and = step down and up through them
+pub fn speed_keyboard_shortcuts(
+    keyboard_input: Res<Input<KeyCode>>,
+    mut simulation_speed: ResMut<SimulationSpeed>,
+) {
+    let number_keys = [
+        KeyCode::Key1,
+        KeyCode::Key2,
+        KeyCode::Key3,
+        KeyCode::Key4,
+        KeyCode::Key5,
+        KeyCode::Key6,
+    ];
+    for (key, speed) in number_keys.iter().zip(SIMULATION_SPEEDS) {
+        if keyboard_input.just_pressed(*key) {
+            simulation_speed.0 = speed;
+        }
+    }
+
+    let current_index = SIMULATION_SPEEDS
+        .iter()
+        .position(|speed| *speed == simulation_speed.0)
+        .unwrap_or(0);
+    if keyboard_input.just_pressed(KeyCode::Minus) && current_index > 0 {
+        simulation_speed.0 = SIMULATION_SPEEDS[current_index - 1];
+    }
+    if keyboard_input.just_pressed(KeyCode::Equals) && current_index + 1 < SIMULATION_SPEEDS.len() {
+        simulation_speed.0 = SIMULATION_SPEEDS[current_index + 1];
+    }
+}
+
+// the only way out of a sandbox match
+pub fn leave_simulation_shortcut(
+    keyboard_input: Res<Input<KeyCode>>,
+    mut next_app_state: ResMut<NextState<AppState>>,
+) {
+    if keyboard_input.just_pressed(KeyCode::Escape) {
+        next_app_state.set(AppState::MainMenu);
+    }
+}
+
+pub fn update_speed_buttons(
+    mut button_query: Query<(&Interaction, &mut BackgroundColor, &SpeedButton)>,
+    simulation_speed: Res<SimulationSpeed>,
 ) {
-    if let Ok(toggle_button) = toggle_button_query.get_single() {
-        commands.entity(toggle_button).despawn_recursive();
+    if !simulation_speed.is_changed() {
+        return;
+    }
+
+    for (interaction, mut background_color, speed_button) in button_query.iter_mut() {
+        if *interaction == Interaction::None {
+            *background_color = speed_button_color(speed_button.speed, simulation_speed.0);
+        }
     }
 }
 
+// the fixed timestep never changes, a faster clock just means more ticks per frame
+pub fn apply_simulation_speed(mut time: ResMut<Time>, simulation_speed: Res<SimulationSpeed>) {
+    if time.relative_speed() != simulation_speed.0 {
+        time.set_relative_speed(simulation_speed.0);
+    }
+}
+
+pub fn reset_simulation_speed(mut time: ResMut<Time>) {
+    time.set_relative_speed(1.0);
+}
+
+// leaving a paused match must not leave the next one frozen
+pub fn reset_play_state(mut next_play_state: ResMut<NextState<PlayState>>) {
+    next_play_state.set(PlayState::Playing);
+}
+
 pub fn play_toggle_interaction(
     mut button_query: Query<
         (&Interaction, &mut BackgroundColor),
@@ -403,8 +1455,14 @@ pub fn play_button_interaction(
     }
 }
 
-// need resource Changed here!
-pub fn spawn_simulation_over_page(mut commands: Commands, asset_server: Res<AssetServer>) {
+pub fn spawn_simulation_over_page(
+    mut commands: Commands,
+    species_assets: Res<SpeciesAssets>,
+    food_chain: Res<FoodChain>,
+    simulation_rng: Res<SimulationRng>,
+    simulation_result: Res<SimulationResult>,
+    simulation_config: Res<SimulationConfig>,
+) {
     commands
         .spawn((
             NodeBundle {
@@ -419,7 +1477,7 @@ pub fn spawn_simulation_over_page(mut commands: Commands, asset_server: Res<Asse
                     sections: vec![TextSection::new(
                         "game over baybay",
                         TextStyle {
-                            font: asset_server.load("fonts/FiraSans-Regular.ttf"),
+                            font: species_assets.font.clone(),
                             font_size: 64.0,
                             color: Color::BLACK,
                         },
@@ -428,9 +1486,129 @@ pub fn spawn_simulation_over_page(mut commands: Commands, asset_server: Res<Asse
                 },
                 ..default()
             });
+            parent.spawn(TextBundle {
+                text: Text {
+                    sections: vec![TextSection::new(
+                        format!(
+                            "{} after {}",
+                            describe_winner(simulation_result.winner, &food_chain),
+                            format_duration(simulation_result.duration)
+                        ),
+                        TextStyle {
+                            font: species_assets.font.clone(),
+                            font_size: 48.0,
+                            color: Color::BLACK,
+                        },
+                    )],
+                    ..default()
+                },
+                ..default()
+            });
+            parent.spawn(TextBundle {
+                text: Text {
+                    sections: vec![TextSection::new(
+                        format!(
+                            "rule: {}{}, seed: {}",
+                            simulation_result.end_condition,
+                            if simulation_result.cut_off {
+                                " (cut off)"
+                            } else {
+                                ""
+                            },
+                            simulation_rng.seed
+                        ),
+                        TextStyle {
+                            font: species_assets.font.clone(),
+                            font_size: 32.0,
+                            color: Color::BLACK,
+                        },
+                    )],
+                    ..default()
+                },
+                ..default()
+            });
+            parent.spawn(TextBundle {
+                text: Text::from_section(
+                    describe_lineage_stats(&simulation_result.stats, &food_chain).join("\n"),
+                    TextStyle {
+                        font: species_assets.font.clone(),
+                        font_size: 24.0,
+                        color: Color::BLACK,
+                    },
+                )
+                .with_alignment(TextAlignment::Center),
+                ..default()
+            });
+            parent.spawn((
+                TextBundle {
+                    text: Text::from_section(
+                        countdown_message(simulation_config.results_countdown),
+                        TextStyle {
+                            font: species_assets.font.clone(),
+                            font_size: 32.0,
+                            color: Color::BLACK,
+                        },
+                    ),
+                    ..default()
+                },
+                CountdownText,
+            ));
+            parent
+                .spawn((
+                    ButtonBundle {
+                        style: Style {
+                            justify_content: JustifyContent::Center,
+                            align_items: AlignItems::Center,
+                            size: Size::new(Val::Px(300.0), Val::Px(80.0)),
+                            ..Style::DEFAULT
+                        },
+                        background_color: BackgroundColor(Color::RED),
+                        ..default()
+                    },
+                    PlayAgainButton,
+                ))
+                .with_children(|parent| {
+                    parent.spawn(TextBundle {
+                        text: Text::from_section(
+                            "Play again",
+                            TextStyle {
+                                font: species_assets.font.clone(),
+                                font_size: 64.0,
+                                color: Color::BLACK,
+                            },
+                        ),
+                        ..default()
+                    });
+                });
         });
 }
 
+fn countdown_message(seconds_left: f32) -> String {
+    format!("back to the menu in {}", seconds_left.ceil())
+}
+
+pub fn play_again_button_interaction(
+    mut button_query: Query<
+        (&Interaction, &mut BackgroundColor),
+        (With<PlayAgainButton>, Changed<Interaction>),
+    >,
+    mut next_app_state: ResMut<NextState<AppState>>,
+) {
+    if let Ok((interaction, mut background_color)) = button_query.get_single_mut() {
+        match *interaction {
+            Interaction::Clicked => {
+                next_app_state.set(AppState::SimulationRunning);
+            }
+            Interaction::Hovered => {
+                *background_color = BackgroundColor(Color::BLUE);
+            }
+            Interaction::None => {
+                *background_color = BackgroundColor(Color::RED);
+            }
+        }
+    }
+}
+
 pub fn despawn_simulation_over_page(
     mut commands: Commands,
     simulation_over_query: Query<Entity, With<SimulationPage>>,
@@ -440,7 +1618,33 @@ pub fn despawn_simulation_over_page(
     }
 }
 
-pub fn spawn_simulation_over_timer(mut commands: Commands, time: Res<Time>) {}
-pub fn despawn_simulation_over_timer(mut commands: Commands, timer: ResMut<SimulationOverTimer>) {}
+pub fn spawn_simulation_over_timer(
+    mut commands: Commands,
+    simulation_config: Res<SimulationConfig>,
+) {
+    commands.insert_resource(SimulationOverTimer {
+        timer: Timer::from_seconds(simulation_config.results_countdown, TimerMode::Once),
+    });
+}
+
+pub fn despawn_simulation_over_timer(mut commands: Commands) {
+    commands.remove_resource::<SimulationOverTimer>();
+}
+
+pub fn tick_simulation_over_timer(
+    mut simulation_over_timer: ResMut<SimulationOverTimer>,
+    mut countdown_text_query: Query<&mut Text, With<CountdownText>>,
+    mut next_app_state: ResMut<NextState<AppState>>,
+    time: Res<Time>,
+) {
+    let timer = &mut simulation_over_timer.timer;
+    timer.tick(time.delta());
+
+    if let Ok(mut countdown_text) = countdown_text_query.get_single_mut() {
+        countdown_text.sections[0].value = countdown_message(timer.remaining_secs());
+    }
 
-pub fn tick_simulation_over_timer(mut simulation_over_timer: ResMut<SimulationOverTimer>) {}
+    if timer.just_finished() {
+        next_app_state.set(AppState::MainMenu);
+    }
+}
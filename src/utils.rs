@@ -1,37 +1,164 @@
-use bevy::prelude::*;
+use bevy::{prelude::*, utils::Duration};
 use rand::prelude::*;
 
-pub const ENTITY_COUNT: u16 = 3;
-pub const ENTITY_MAX_SPEED: f32 = 10.0;
+use crate::components::Species;
+use crate::resources::{Arena, FoodChain, LineageStats, SpatialGrid};
+use crate::ruleset::Ruleset;
+
+pub const DEFAULT_ENTITY_COUNT: u16 = 3;
+pub const DEFAULT_RULESET: &str = "rps";
+pub const FONT_PATH: &str = "fonts/FiraSans-Regular.ttf";
+pub const MAX_ENTITY_COUNT: u16 = 500;
+// units per second
+pub const ENTITY_MAX_SPEED: f32 = 150.0;
+// the most steering can change velocity by, in units per second, per second
+pub const ENTITY_MAX_FORCE: f32 = 300.0;
+// degrees per second
+pub const ENTITY_MAX_TURN_RATE: f32 = 240.0;
+// how hard entities turn towards where they want to go, per radian they are off by
+pub const TURN_RESPONSIVENESS: f32 = 8.0;
+// how quickly sprites turn to face where they are going, per second
+pub const SPRITE_TURN_RATE: f32 = 10.0;
+// below this speed, in units per second, sprites stop turning so they don't jitter in place
+pub const MIN_FACING_SPEED: f32 = 5.0;
+// which way the art in `assets/sprites` points, in degrees counterclockwise from the right
+pub const DEFAULT_SPRITE_FORWARD: f32 = 90.0;
+// chasers start easing off this close to their prey so they don't overshoot it
+pub const ARRIVE_SLOWING_RADIUS: f32 = ENTITY_SIZE;
+// how far away predators and prey can still be seen
+pub const PERCEPTION_RADIUS: f32 = ENTITY_SIZE * 6.0;
+// the most a ruleset may ask for, since neighbour lookups visit every grid cell this far around
+pub const MAX_PERCEPTION_RADIUS: f32 = ENTITY_SIZE * 20.0;
+// likewise, as lookups also widen by how far the fastest species moves in a tick
+pub const MAX_SPEED: f32 = ENTITY_MAX_SPEED * 10.0;
+// how wide an entity's view is, centered on where it faces
+pub const FIELD_OF_VIEW_DEGREES: f32 = 270.0;
+// fraction of top speed to wander around at when nothing is in view
+pub const WANDER_SPEED: f32 = 0.5;
+// wandering aims for a circle this far ahead, the bigger it is the sharper the turns
+pub const WANDER_DISTANCE: f32 = ENTITY_SIZE * 2.0;
+pub const WANDER_RADIUS: f32 = ENTITY_SIZE;
+// the most the aim can drift around that circle, in radians per second
+pub const WANDER_JITTER: f32 = 6.0;
 pub const ENTITY_SIZE: f32 = 64.0;
 pub const ENTITY_HALF_SIZE: f32 = 32.0;
-pub const ENTITY_ACCELERATION: f32 = 1.0;
-pub const TIME_FACTOR: f32 = 1.0;
+pub const SPATIAL_GRID_CELL_SIZE: f32 = ENTITY_SIZE * 2.0;
+// entities start turning away from a solid wall this close to it
+pub const WALL_AVOIDANCE_DISTANCE: f32 = ENTITY_SIZE * 2.0;
+// how quickly the camera catches up with the crowd in an infinite arena, per second
+pub const CAMERA_FOLLOW_RATE: f32 = 2.0;
+// a crowded arena may have no free spot left, so stop looking for one eventually
+pub const MAX_SPAWN_ATTEMPTS: u16 = 100;
+// how long a caught entity takes to turn into its captor's species, in seconds
+pub const CONVERSION_DURATION: f32 = 0.6;
+// how far past full size a converted entity pops before settling
+pub const CONVERSION_POP: f32 = 0.3;
+// frames per second for every clip except converting, which lasts as long as the conversion
+pub const ANIMATION_FPS: f32 = 8.0;
+// frames per clip in a sprite sheet, unless its ruleset says otherwise
+pub const DEFAULT_SHEET_FRAMES: usize = 4;
+pub const MAX_SHEET_FRAMES: usize = 64;
+pub const DEFAULT_TICK_RATE: f64 = 60.0;
+// slower than this a single tick moves entities right past each other
+pub const MIN_TICK_RATE: f64 = 1.0;
+// faster than this the fixed update can't keep up and never lets a frame finish
+pub const MAX_TICK_RATE: f64 = 1000.0;
+pub const DEFAULT_RESULTS_COUNTDOWN: f32 = 10.0;
+pub const DEFAULT_TIME_LIMIT: f32 = 60.0;
+// headless matches are cut off after this many seconds unless `--max-duration` says otherwise
+pub const DEFAULT_MAX_DURATION: f32 = 600.0;
+pub const SIMULATION_SPEEDS: [f32; 6] = [0.25, 0.5, 1.0, 2.0, 4.0, 16.0];
 
-pub fn generate_random_vec3_in_bounds(bounds: &Window) -> Vec3 {
-    let random_x = random::<f32>() * bounds.width();
-    let random_y = random::<f32>() * bounds.height();
+pub fn generate_random_vec3_in_bounds(bounds: &Arena, rng: &mut impl Rng) -> Vec3 {
+    let random_x = bounds.origin.x + rng.gen::<f32>() * bounds.width;
+    let random_y = bounds.origin.y + rng.gen::<f32>() * bounds.height;
     Vec3::new(random_x, random_y, 0.0)
 }
 
-pub fn generate_exclusive_transform(bounds: &Window, taken_positions: &mut Vec<Vec3>) -> Transform {
+// somewhere nobody in `taken_positions` is standing, if one turns up in time
+pub fn generate_exclusive_transform(
+    bounds: &Arena,
+    rng: &mut impl Rng,
+    taken_positions: &SpatialGrid,
+) -> Transform {
+    let mut attempts = 0;
     let the_right_place = loop {
-        let random_vec3 = generate_random_vec3_in_bounds(bounds);
-        if !vec3_conflicts_with_existing(random_vec3.clone(), &taken_positions) {
+        let random_vec3 = generate_random_vec3_in_bounds(bounds, rng);
+        attempts += 1;
+        if attempts >= MAX_SPAWN_ATTEMPTS
+            || !vec3_conflicts_with_existing(random_vec3, taken_positions)
+        {
             break random_vec3;
         }
     };
 
-    taken_positions.push(the_right_place);
-
     Transform::from_xyz(the_right_place.x, the_right_place.y, 0.0)
 }
 
-pub fn vec3_conflicts_with_existing(vec3: Vec3, taken_positions: &Vec<Vec3>) -> bool {
-    for position in taken_positions {
-        if position.distance(vec3) < (ENTITY_SIZE + 5.0) {
-            return true;
-        }
+pub fn vec3_conflicts_with_existing(vec3: Vec3, taken_positions: &SpatialGrid) -> bool {
+    taken_positions
+        .nearby(vec3, ENTITY_SIZE + 5.0)
+        .any(|taken| taken.position.distance(vec3) < (ENTITY_SIZE + 5.0))
+}
+
+// e.g. "42.3s" or "2m 05.0s"
+pub fn format_duration(duration: Duration) -> String {
+    let seconds = duration.as_secs_f32();
+    let minutes = (seconds / 60.0).floor();
+    if minutes > 0.0 {
+        format!("{}m {:04.1}s", minutes, seconds - minutes * 60.0)
+    } else {
+        format!("{:.1}s", seconds)
+    }
+}
+
+pub fn describe_winner(winner: Option<Species>, food_chain: &FoodChain) -> String {
+    match winner {
+        Some(winner) => format!("{} wins", food_chain.name(winner)),
+        None => "it's a draw".to_string(),
+    }
+}
+
+// e.g. "longest-surviving original rock: 12.3s", one line per stat
+pub fn describe_lineage_stats(stats: &LineageStats, food_chain: &FoodChain) -> Vec<String> {
+    let mut lines: Vec<String> = stats
+        .longest_surviving
+        .iter()
+        .map(|survivor| {
+            let lasted = if survivor.never_caught {
+                "never caught".to_string()
+            } else {
+                format_duration(survivor.lasted)
+            };
+            format!(
+                "longest-surviving original {}: {}",
+                food_chain.name(survivor.species),
+                lasted
+            )
+        })
+        .collect();
+    if let Some((original, became)) = &stats.most_converted {
+        let became: Vec<&str> = became.iter().map(|s| food_chain.name(*s)).collect();
+        lines.push(format!(
+            "most converted: started out as {}, caught {} times (became {})",
+            food_chain.name(*original),
+            became.len(),
+            became.join(", then ")
+        ));
     }
-    false
+    if let Some((original, catches)) = stats.best_hunter {
+        lines.push(format!(
+            "best hunter: started out as {}, caught {} others",
+            food_chain.name(original),
+            catches
+        ));
+    }
+    lines
+}
+
+// rulesets are known by their file name, e.g. `rps` for `rulesets/rps.ron`
+pub fn ruleset_name(asset_server: &AssetServer, handle: &Handle<Ruleset>) -> Option<String> {
+    let path = asset_server.get_handle_path(handle)?;
+    let name = path.path().file_stem()?.to_str()?;
+    Some(name.to_string())
 }